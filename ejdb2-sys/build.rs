@@ -49,16 +49,42 @@ fn config_and_build(is_static: bool) -> Result<()> {
     let install_dir = if let Ok(install_dir) = env::var("EJDB2_INSTALL_PATH") {
         eprintln!("use pre-build ejdb2: {}", install_dir);
         PathBuf::from(install_dir)
-    } else {
+    } else if let Ok(source_dir) = env::var("EJDB2_SOURCE") {
         eprintln!("build ejdb2 from source");
-        let source_dir = env::var("EJDB2_SOURCE").unwrap();
         build_source(source_dir, is_static)?
+    } else {
+        eprintln!("probe system-installed ejdb2 by pkg-config");
+        let include_dir = probe_system(is_static).with_context(|| {
+            "ejdb2 not found by pkg-config, please specify EJDB2_INSTALL_PATH or EJDB2_SOURCE"
+        })?;
+        return gen_binding(&include_dir);
     };
     link_libs(&install_dir, is_static)?;
-    gen_binding(&install_dir)?;
+    gen_binding(&install_dir.join("include"))?;
     Ok(())
 }
 
+/// probe system-installed ejdb2 by pkg-config, which also emits link flags;
+/// returns include dir containing `ejdb2/ejdb2.h`
+fn probe_system(is_static: bool) -> Result<PathBuf> {
+    let lib = pkg_config::Config::new()
+        .statik(is_static)
+        .probe("libejdb2")
+        .or_else(|_| pkg_config::Config::new().statik(is_static).probe("ejdb2"))?;
+    // system include dirs are usually omitted by pkg-config
+    let defaults = [
+        PathBuf::from("/usr/include"),
+        PathBuf::from("/usr/local/include"),
+    ];
+    lib.include_paths
+        .iter()
+        .flat_map(|p| p.ancestors().take(2))
+        .chain(defaults.iter().map(|p| p.as_path()))
+        .find(|p| p.join("ejdb2").join("ejdb2.h").exists())
+        .map(|p| p.to_path_buf())
+        .ok_or_else(|| anyhow!("ejdb2/ejdb2.h not found"))
+}
+
 fn link_libs(dst: &PathBuf, is_static: bool) -> Result<()> {
     println!(
         "cargo:rustc-link-search=native={}",
@@ -212,9 +238,9 @@ fn patch_headers(out_dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn gen_binding(dst: &PathBuf) -> Result<()> {
-    let header_file = dst
-        .join("include/ejdb2/ejdb2.h")
+fn gen_binding(include_dir: &Path) -> Result<()> {
+    let header_file = include_dir
+        .join("ejdb2/ejdb2.h")
         .as_path()
         .to_str()
         .unwrap()
@@ -224,13 +250,15 @@ fn gen_binding(dst: &PathBuf) -> Result<()> {
 
     let bindings = bindgen::Builder::default()
         .header(header_file)
-        .clang_arg("-I".to_owned() + dst.join("include").as_path().to_str().unwrap())
+        .clang_arg("-I".to_owned() + include_dir.to_str().unwrap())
         //.clang_arg("-IE:/msys64/usr/include")
         .enable_function_attribute_detection()
         .derive_default(true)
         .rustified_enum(".*")
         .whitelist_type("(EJDB|JBL|JBR|ejdb|jbl|jbp|jbr|re|iwkv)(_.*?)?")
-        .whitelist_function("(ejdb|jbl|jbp|jbn|jql|jbr|lwre|iwxstr|iwlog)_.*")
+        .whitelist_type("iw_ecode")
+        .whitelist_function("(ejdb|jbl|jbp|jbn|jql|jbr|lwre|iwxstr|iwlog|iwkv|iwpool)_.*")
+        .whitelist_function("iwlog")
         .opaque_type("_JBL_iterator")
         .rustfmt_bindings(true)
         .generate()
@@ -4,10 +4,32 @@ use ejdb2_sys as sys;
 use rand::RngCore;
 
 /// builder to build database object
+///
+/// ## Memory tuning
+///
+/// The storage file is memory mapped by iowow, which sizes and grows the
+/// mapping itself; EJDB2 options have no field for initial file size or
+/// mmap window, so they can not be tuned here. Options affecting memory:
+/// - `document_buffer_sz`: buffer of each doc read by a query, raise it
+///   for large docs in read-heavy workloads
+/// - `sort_buffer_sz`: in-memory sorting of `asc`/`desc` queries, larger
+///   results are sorted in a temp file
+/// - `wal_buffer_sz` and `wal_checkpoint_buffer_sz`: write buffers, which
+///   only take effect with `wal(true)`
+///
+/// Zero keeps the default of an option; values below the documented
+/// minimum are raised to it by EJDB2.
 pub struct EJDB2Builder {
     ejdb_opts: sys::EJDB_OPTS,
     db_path: XString,
     http_host: Option<XString>,
+    create_if_missing: bool,
+    #[cfg(feature = "std")]
+    mtime_field: Option<XString>,
+    #[cfg(feature = "std")]
+    open_timeout: Option<core::time::Duration>,
+    #[cfg(feature = "std")]
+    temporary: bool,
 }
 
 impl EJDB2Builder {
@@ -21,18 +43,136 @@ impl EJDB2Builder {
             ejdb_opts,
             db_path: path,
             http_host: None,
+            create_if_missing: true,
+            #[cfg(feature = "std")]
+            mtime_field: None,
+            #[cfg(feature = "std")]
+            open_timeout: None,
+            #[cfg(feature = "std")]
+            temporary: false,
         }
     }
 
-    /// build database object
-    pub fn build(self) -> Result<Database> {
+    /// builder of a database in a new file under temp dir, which is
+    /// deleted along with its WAL file when the database is dropped;
+    /// storage of EJDB2 is always file backed, there is no in-memory mode
+    #[cfg(feature = "std")]
+    pub fn temporary() -> Self {
+        let name = format!(
+            "ejdb2-tmp-{}-{:x}",
+            std::process::id(),
+            rand::thread_rng().next_u64()
+        );
+        let path = std::env::temp_dir().join(name);
+        let mut builder = Self::new(path.to_string_lossy().as_ref());
+        builder.temporary = true;
+        builder.oflags(DatabaseOpenMode::IWKV_TRUNC)
+    }
+
+    /// build database object;
+    /// fails with `EjdbError::HttpBindError` if HTTP endpoint can not be bound
+    pub fn build(mut self) -> Result<Database> {
         let rc = unsafe { sys::ejdb_init() };
         if rc != 0 {
             return Err(EjdbError::InitError(rc));
         }
 
+        #[cfg(feature = "std")]
+        {
+            if !self.create_if_missing && !db_exists(&self.db_path) {
+                return Err(EjdbError::DatabaseNotFound(self.db_path));
+            }
+        }
+
+        #[cfg(all(feature = "std", not(windows)))]
+        {
+            if self.ejdb_opts.http.enabled && self.ejdb_opts.http.port == 0 {
+                let port = pick_http_port(self.http_host.as_ref())?;
+                self.ejdb_opts.http.port = port as i32;
+            }
+        }
+
         //println!("Running EJDB with options: {:#?}", &ejdb_opts);
-        Database::new(self.db_path, self.http_host, self.ejdb_opts)
+        #[cfg(feature = "std")]
+        let open_timeout = self.open_timeout;
+        #[cfg(not(feature = "std"))]
+        let open_timeout = None;
+        if open_timeout.is_some() {
+            self.ejdb_opts.kv.file_lock_fail_fast = true;
+        }
+        // delete files if open fails as well
+        #[cfg(feature = "std")]
+        let temp_file = if self.temporary {
+            Some(database::TempFile::new(&self.db_path))
+        } else {
+            None
+        };
+        #[cfg(all(feature = "std", not(windows)))]
+        let http = if self.ejdb_opts.http.enabled {
+            let host = self.http_host.as_ref().map(|h| String::from(h.as_str()));
+            Some((host, self.ejdb_opts.http.port as u16))
+        } else {
+            None
+        };
+        let res = Database::new(self.db_path, self.http_host, self.ejdb_opts, open_timeout);
+        #[cfg(all(feature = "std", not(windows)))]
+        let res = match (res, http) {
+            (Err(e @ EjdbError::OpenError { .. }), Some((host, port))) => {
+                // EJDB2 reports no cause, tell if the port is taken
+                Err(check_http_bindable(host.as_deref(), port)
+                    .err()
+                    .unwrap_or(e))
+            }
+            (res, _) => res,
+        };
+        #[allow(unused_mut)]
+        let mut db = res?;
+        #[cfg(feature = "std")]
+        {
+            db.mtime_field = self.mtime_field;
+            db.temp_file = temp_file;
+        }
+        #[cfg(all(feature = "std", not(windows)))]
+        {
+            if let Some(port) = db.http_port() {
+                check_http_listening(db.http_host.as_ref(), port)?;
+            }
+        }
+        Ok(db)
+    }
+    /// build database opened read-only, see [`ReadOnlyDatabase`];
+    /// `IWKV_RDONLY` is added to open flags
+    ///
+    /// [`ReadOnlyDatabase`]: crate::database::ReadOnlyDatabase
+    pub fn build_read_only(mut self) -> Result<database::ReadOnlyDatabase> {
+        self.ejdb_opts.kv.oflags |= DatabaseOpenMode::IWKV_RDONLY.bits();
+        self.build().map(database::ReadOnlyDatabase)
+    }
+    /// seed of random generator used by storage engine;
+    /// a random seed is used if not specified
+    #[inline]
+    pub fn random_seed(mut self, seed: u32) -> Self {
+        self.ejdb_opts.kv.random_seed = seed;
+        self
+    }
+    /// create database file if not existing, default: true;
+    /// if false, build fails with `EjdbError::DatabaseNotFound` when neither
+    /// database file nor its WAL file exists
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Self {
+        self.create_if_missing = create_if_missing;
+        self
+    }
+    /// stamp given field, e.g. `_mtime`, of JSON object with current
+    /// epoch millis on every `put`, `patch` and `merge_or_put`, default: off;
+    /// it is done by this crate before storing, not by EJDB2, so docs
+    /// written by `apply` queries or other clients are not stamped
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn track_mtime<T: Into<XString>>(mut self, field: T) -> Self {
+        self.mtime_field = Some(field.into());
+        self
     }
     /// bitmask of database file open modes
     #[inline]
@@ -40,18 +180,69 @@ impl EJDB2Builder {
         self.ejdb_opts.kv.oflags = oflags.bits();
         self
     }
-    /// do not wait and raise error if database is locked by another process
+    /// trim unused tail of database file on close, default: true;
+    /// call it after `oflags`, which replaces all open flags
+    #[inline]
+    pub fn trim_on_close(mut self, trim: bool) -> Self {
+        let mut oflags = DatabaseOpenMode::from_bits_truncate(self.ejdb_opts.kv.oflags);
+        oflags.set(DatabaseOpenMode::IWKV_NO_TRIM_ON_CLOSE, !trim);
+        self.ejdb_opts.kv.oflags = oflags.bits();
+        self
+    }
+    /// do not wait and fail with `EjdbError::Locked` if database is locked
+    /// by another process
     #[inline]
     pub fn file_lock_fail_fast(mut self, file_lock_fail_fast: bool) -> Self {
         self.ejdb_opts.kv.file_lock_fail_fast = file_lock_fail_fast;
         self
     }
+    /// wait up to `timeout` for database locked by another process or open
+    /// in this process, then fail with `EjdbError::Locked`; implies
+    /// `file_lock_fail_fast`, as the storage engine can either block
+    /// forever or fail at once; the file is checked again after a delay
+    /// starting at 10ms and doubling, as [`retry`](crate::util::retry) does
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn open_timeout(mut self, timeout: core::time::Duration) -> Self {
+        self.open_timeout = Some(timeout);
+        self
+    }
     /// use write-ahead-log or not, default: false
     #[inline]
     pub fn wal(mut self, wal: bool) -> Self {
         self.ejdb_opts.no_wal = !wal;
         self
     }
+    /// check CRC32 sum of data blocks during checkpoint, default: false
+    #[inline]
+    pub fn wal_check_crc_on_checkpoint(mut self, check_crc: bool) -> Self {
+        self.ejdb_opts.kv.wal.check_crc_on_checkpoint = check_crc;
+        self
+    }
+    /// WAL savepoint timeout in seconds, default: 10 sec
+    #[inline]
+    pub fn wal_savepoint_timeout_sec(mut self, timeout: u32) -> Self {
+        self.ejdb_opts.kv.wal.savepoint_timeout_sec = timeout;
+        self
+    }
+    /// WAL checkpoint timeout in seconds, default: 300 sec (5 min)
+    #[inline]
+    pub fn wal_checkpoint_timeout_sec(mut self, timeout: u32) -> Self {
+        self.ejdb_opts.kv.wal.checkpoint_timeout_sec = timeout;
+        self
+    }
+    /// WAL file intermediate buffer size, default: 8Mb
+    #[inline]
+    pub fn wal_buffer_sz(mut self, wal_buffer_sz: usize) -> Self {
+        self.ejdb_opts.kv.wal.wal_buffer_sz = wal_buffer_sz as _;
+        self
+    }
+    /// WAL checkpoint buffer size in bytes, default: 1Gb
+    #[inline]
+    pub fn wal_checkpoint_buffer_sz(mut self, checkpoint_buffer_sz: u64) -> Self {
+        self.ejdb_opts.kv.wal.checkpoint_buffer_sz = checkpoint_buffer_sz;
+        self
+    }
     /// max sorting buffer size, default 16Mb, min 1Mb
     #[inline]
     pub fn sort_buffer_sz(mut self, sort_buffer_sz: u32) -> Self {
@@ -65,6 +256,11 @@ impl EJDB2Builder {
         self
     }
 
+    /// enable HTTP/Websocket endpoint in background; with port 0 a free
+    /// port is picked, see [`Database::http_port`]. `build` checks the
+    /// endpoint accepts connections and fails with `EjdbError::HttpBindError`
+    /// otherwise, e.g. if a picked port is taken by another process before
+    /// EJDB2 binds it
     #[cfg(not(windows))]
     #[inline]
     pub fn enable_http<T: Into<XString>>(
@@ -85,3 +281,107 @@ impl EJDB2Builder {
         self
     }
 }
+
+/// pick a free port for HTTP endpoint, as EJDB2 neither picks nor reports
+/// the port it listens on; the port is released before EJDB2 binds it,
+/// so another process might take it meanwhile, see `check_http_listening`
+#[cfg(all(feature = "std", not(windows)))]
+fn pick_http_port(host: Option<&XString>) -> Result<u16> {
+    // free on all interfaces, whichever EJDB2 binds by default
+    let host = host.map(|h| h.as_str()).unwrap_or("0.0.0.0");
+    let listener = std::net::TcpListener::bind((host, 0))
+        .map_err(|error| EjdbError::HttpBindError { port: 0, error })?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// check `port` can be bound, to explain failed open of database
+#[cfg(all(feature = "std", not(windows)))]
+fn check_http_bindable(host: Option<&str>, port: u16) -> Result<()> {
+    let host = host.unwrap_or("0.0.0.0");
+    match std::net::TcpListener::bind((host, port)) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(EjdbError::HttpBindError { port, error }),
+    }
+}
+
+/// check HTTP endpoint of opened database accepts connections on `port`
+#[cfg(all(feature = "std", not(windows)))]
+fn check_http_listening(host: Option<&XString>, port: u16) -> Result<()> {
+    let host = host.map(|h| h.as_str()).unwrap_or("127.0.0.1");
+    let timeout = core::time::Duration::from_secs(1);
+    let res = std::net::ToSocketAddrs::to_socket_addrs(&(host, port)).and_then(|mut addrs| {
+        let addr = addrs
+            .next()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::AddrNotAvailable))?;
+        std::net::TcpStream::connect_timeout(&addr, timeout)
+    });
+    res.map(|_| ())
+        .map_err(|error| EjdbError::HttpBindError { port, error })
+}
+
+/// database exists if either data file or WAL file exists,
+/// as data can be recovered from WAL file on open
+#[cfg(feature = "std")]
+#[inline]
+fn db_exists(path: &XString) -> bool {
+    let path = path.as_str();
+    std::path::Path::new(path).exists() || std::path::Path::new(&format!("{}-wal", path)).exists()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_create_if_missing() {
+        let file = crate::test::tmp_file("not_existing");
+        let res = EJDB2Builder::new(file.as_str())
+            .create_if_missing(false)
+            .build();
+        assert!(matches!(res, Err(EjdbError::DatabaseNotFound(_))));
+    }
+
+    #[test]
+    fn test_temporary() {
+        crate::test::catch(|| {
+            let db = EJDB2Builder::temporary().build()?;
+            db.put("c1", "{\"a\":1}", Some(1))?;
+            let path = std::path::PathBuf::from(db.path());
+            assert!(path.exists());
+            let db = db.reopen(DatabaseOpenMode::empty())?;
+            assert!(db.exists("c1", 1)?);
+            drop(db);
+            assert!(!path.exists());
+            let db = EJDB2Builder::temporary().build()?;
+            let path = std::path::PathBuf::from(db.path());
+            db.close()?;
+            assert!(!path.exists());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_http_port() {
+        let file = crate::test::tmp_file("http");
+        let res = crate::test::catch(|| {
+            let db = EJDB2Builder::new(file.as_str())
+                .enable_http(0, None::<&str>, true)
+                .build()?;
+            let port = db.http_port().unwrap();
+            assert!(port > 0);
+            let busy = std::net::TcpListener::bind(("localhost", port));
+            assert!(busy.is_err());
+            let other = crate::test::tmp_file("http2");
+            let res = EJDB2Builder::new(other.as_str())
+                .enable_http(port, None::<&str>, true)
+                .build();
+            crate::test::remove_db_files(&other);
+            assert!(matches!(res, Err(EjdbError::HttpBindError { .. })));
+            Ok(())
+        });
+        crate::test::remove_db_files(&file);
+        res.unwrap();
+    }
+}
@@ -1,21 +1,202 @@
 use crate::{
-    exec::Query,
+    exec::{JsonDoc, Query},
     jbl::JBL,
     jql::JQL,
     printer::AsJson,
-    utils::check_rc,
+    utils::{self, check_rc},
     xstr::{StringPtr, XString},
-    EjdbError, JsonPrintFlags, Result,
+    DatabaseOpenMode, EjdbError, IndexMode, JsonPrintFlags, Result,
 };
-use core::ptr;
+use core::{
+    cell::Cell,
+    mem, ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+    time::Duration,
+};
+
+#[cfg(feature = "std")]
+use crate::jbl::{self, JsonType};
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{boxed::Box, string::String, vec::Vec};
 
 use ejdb2_sys as sys;
 
+/// hook to report failure of closing database on drop
+pub type CloseErrorHook = fn(&EjdbError);
+
+static CLOSE_ERROR_HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// set hook to report failure of closing database on drop, `None` to reset;
+/// by default the error is printed to stderr if `std` is enabled, otherwise ignored
+#[inline]
+pub fn set_close_error_hook(hook: Option<CloseErrorHook>) {
+    let p = hook.map_or(ptr::null_mut(), |f| f as *mut ());
+    CLOSE_ERROR_HOOK.store(p, Ordering::SeqCst);
+}
+
+fn report_close_error(e: &EjdbError) {
+    let p = CLOSE_ERROR_HOOK.load(Ordering::SeqCst);
+    if !p.is_null() {
+        let hook: CloseErrorHook = unsafe { mem::transmute(p) };
+        return hook(e);
+    }
+    #[cfg(feature = "std")]
+    eprintln!("failed to close EJDB2 database: {}", e);
+}
+
+/// ## Concurrency model
+///
+/// A `Database` handle is used from one thread at a time, and its operations
+/// are tracked by scope:
+/// - a read scope is entered by [`Database::read_txn`] and while a query is
+///   being iterated; read scopes can be nested
+/// - a write scope is entered by [`Database::write_txn`]; it can not be nested
+///   and can not be entered within a read scope
+///
+/// Any write (`put`, `patch`, `del`, collection and index changes) within a
+/// read scope, also while a query is being iterated within a write scope,
+/// returns [`EjdbError::TxnConflict`] instead of blocking on the lock held
+/// by the running query.
+///
+/// EJDB2 itself is internally synchronized with read-write locks, so the
+/// handle is `Send` and can be moved to another thread. It is not `Sync`
+/// as scopes are tracked per handle without locking; use
+/// [`SharedDatabase`] to share it between threads.
+///
+/// ## File locking
+///
+/// The database file is locked by the storage engine while open. Another
+/// process opening it blocks until the lock is released, or fails with
+/// [`EjdbError::Locked`] if `file_lock_fail_fast` or `open_timeout` is set
+/// on the builder. File locks do not exclude opens within a process; only
+/// with `open_timeout` set, an open of a file already open in this process
+/// waits for it to be closed and fails with [`EjdbError::Locked`] as well.
+/// Without it, the file is opened again as by EJDB2 itself.
 pub struct Database {
     ptr: sys::EJDB,
     pub(crate) ejdb_opts: sys::EJDB_OPTS,
     pub(crate) db_path: XString,
     pub(crate) http_host: Option<XString>,
+    /// port of HTTP endpoint if enabled
+    http_port: Option<u16>,
+    /// field stamped with epoch millis on write
+    #[cfg(feature = "std")]
+    pub(crate) mtime_field: Option<XString>,
+    /// how long to wait for a locked file on open
+    open_timeout: Option<Duration>,
+    /// registration of the file in this process, released after close
+    file_guard: Option<FileGuard>,
+    /// files deleted after close, see `EJDB2Builder::temporary`
+    #[cfg(feature = "std")]
+    pub(crate) temp_file: Option<TempFile>,
+    txn: Cell<TxnState>,
+}
+
+/// first delay to check if a locked file is released, doubled on each retry
+#[cfg(feature = "std")]
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// paths of databases open in this process, once for each handle
+#[cfg(feature = "std")]
+static OPEN_FILES: std::sync::Mutex<Vec<std::path::PathBuf>> = std::sync::Mutex::new(Vec::new());
+
+/// registration of an open database file in this process
+#[cfg(feature = "std")]
+struct FileGuard(std::path::PathBuf);
+
+#[cfg(feature = "std")]
+impl FileGuard {
+    /// register file; `None` if `exclusive` and already open in this process
+    fn acquire(path: &XString, exclusive: bool) -> Option<Self> {
+        let path = abs_path(path.as_str());
+        let mut files = OPEN_FILES.lock().unwrap_or_else(|e| e.into_inner());
+        if exclusive && files.contains(&path) {
+            return None;
+        }
+        files.push(path.clone());
+        Some(Self(path))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for FileGuard {
+    fn drop(&mut self) {
+        let mut files = OPEN_FILES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pos) = files.iter().position(|p| p == &self.0) {
+            files.swap_remove(pos);
+        }
+    }
+}
+
+/// database file and its WAL file, deleted on drop
+#[cfg(feature = "std")]
+pub(crate) struct TempFile(std::path::PathBuf);
+
+#[cfg(feature = "std")]
+impl TempFile {
+    pub(crate) fn new(path: &XString) -> Self {
+        Self(path.as_str().into())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let mut wal = self.0.clone().into_os_string();
+        wal.push("-wal");
+        let _ = std::fs::remove_file(&self.0);
+        let _ = std::fs::remove_file(wal);
+    }
+}
+
+/// files are not tracked without `std`
+#[cfg(not(feature = "std"))]
+struct FileGuard;
+
+#[cfg(not(feature = "std"))]
+impl FileGuard {
+    #[inline(always)]
+    fn acquire(_path: &XString, _exclusive: bool) -> Option<Self> {
+        Some(Self)
+    }
+}
+
+/// absolute path of a file which might not exist yet
+#[cfg(feature = "std")]
+fn abs_path(path: &str) -> std::path::PathBuf {
+    use std::{fs, path::Path};
+    let path = Path::new(path);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match (fs::canonicalize(dir), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_owned(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TxnState {
+    Idle,
+    Read(usize),
+    /// number of queries iterating or read scopes within the write scope
+    Write {
+        reading: usize,
+    },
+}
+
+/// restores previous scope state on drop
+pub(crate) struct TxnGuard<'a> {
+    db: &'a Database,
+    prev: TxnState,
+}
+
+impl Drop for TxnGuard<'_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.db.txn.set(self.prev);
+    }
 }
 
 impl Database {
@@ -24,25 +205,227 @@ impl Database {
         db_path: XString,
         http_host: Option<XString>,
         ejdb_opts: sys::EJDB_OPTS,
+        open_timeout: Option<Duration>,
     ) -> Result<Self> {
-        let mut ptr = ptr::null_mut();
-        let rc = unsafe { sys::ejdb_open(&ejdb_opts, &mut ptr) };
-        if rc != 0 {
-            return Err(EjdbError::OpenError { rc, file: db_path });
-        }
+        #[cfg(feature = "std")]
+        let opened = match open_timeout {
+            Some(timeout) => crate::util::retry_locked(
+                || Self::open_raw(&db_path, &ejdb_opts, true),
+                timeout,
+                LOCK_RETRY_INTERVAL,
+            ),
+            None => Self::open_raw(&db_path, &ejdb_opts, false),
+        };
+        #[cfg(not(feature = "std"))]
+        let opened = Self::open_raw(&db_path, &ejdb_opts, false);
+        let (ptr, file_guard) = match opened {
+            Ok(v) => v,
+            Err(EjdbError::Locked(_)) => return Err(EjdbError::Locked(db_path)),
+            Err(EjdbError::Generic(rc)) => return Err(EjdbError::OpenError { rc, file: db_path }),
+            Err(e) => return Err(e),
+        };
+        let http_port = if ejdb_opts.http.enabled {
+            Some(ejdb_opts.http.port as u16)
+        } else {
+            None
+        };
         Ok(Self {
             ptr,
             ejdb_opts,
             db_path,
             http_host,
+            http_port,
+            #[cfg(feature = "std")]
+            mtime_field: None,
+            #[cfg(feature = "std")]
+            temp_file: None,
+            open_timeout,
+            file_guard: Some(file_guard),
+            txn: Cell::new(TxnState::Idle),
         })
     }
 
+    /// open database once; fails with `EjdbError::Locked` if the file is
+    /// locked by another process, or if `exclusive` and open in this process
+    fn open_raw(
+        db_path: &XString,
+        ejdb_opts: &sys::EJDB_OPTS,
+        exclusive: bool,
+    ) -> Result<(sys::EJDB, FileGuard)> {
+        let guard = match FileGuard::acquire(db_path, exclusive) {
+            Some(v) => v,
+            None => return Err(EjdbError::Locked(XString::new())),
+        };
+        let mut ptr = ptr::null_mut();
+        let rc = unsafe { sys::ejdb_open(ejdb_opts, &mut ptr) };
+        if utils::is_locked(rc) {
+            return Err(EjdbError::Locked(XString::new()));
+        }
+        check_rc(rc)?;
+        Ok((ptr, guard))
+    }
+
     #[inline(always)]
     pub(crate) fn raw_ptr(&self) -> sys::EJDB {
         self.ptr
     }
 
+    /// path of database file as given to the builder; EJDB2 never renames
+    /// or moves the file, so it stays valid for the lifetime of the handle
+    #[inline]
+    pub fn path(&self) -> &str {
+        self.db_path.as_str()
+    }
+
+    /// port the HTTP endpoint listens on, `None` if not enabled
+    #[inline]
+    pub fn http_port(&self) -> Option<u16> {
+        self.http_port
+    }
+
+    /// size in bytes of database file on disk, excluding the WAL file
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn file_size(&self) -> Result<u64> {
+        let meta = std::fs::metadata(self.path())?;
+        Ok(meta.len())
+    }
+
+    /// close database and return error if failed, e.g. pending writes
+    /// can not be flushed; recommended over drop, which only reports
+    /// the error to the hook set by [`set_close_error_hook`]
+    #[inline]
+    pub fn close(mut self) -> Result<()> {
+        let rc = unsafe { sys::ejdb_close(&mut self.ptr) };
+        // handle is released even if failed
+        self.ptr = ptr::null_mut();
+        check_rc(rc)
+    }
+
+    /// close database and open it again with same options but given open flags
+    fn reopen_with(mut self, oflags: u8) -> Result<Self> {
+        let rc = unsafe { sys::ejdb_close(&mut self.ptr) };
+        self.ptr = ptr::null_mut();
+        check_rc(rc)?;
+        let mut ejdb_opts = self.ejdb_opts;
+        ejdb_opts.kv.oflags = oflags;
+        // options point into buffers of path and host, which are moved along
+        let db_path = mem::take(&mut self.db_path);
+        let http_host = self.http_host.take();
+        // release registration of the file before it is opened again
+        drop(self.file_guard.take());
+        #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+        let mut db = Self::new(db_path, http_host, ejdb_opts, self.open_timeout)?;
+        #[cfg(feature = "std")]
+        {
+            db.mtime_field = self.mtime_field.take();
+            db.temp_file = self.temp_file.take();
+        }
+        Ok(db)
+    }
+
+    /// close database and open it again with same options but given open mode,
+    /// e.g. switch to read-only; beware `IWKV_TRUNC` clears the database
+    #[inline]
+    pub fn reopen(self, mode: DatabaseOpenMode) -> Result<Self> {
+        self.reopen_with(mode.bits())
+    }
+
+    /// compact database file by trimming its unused tail, which is done by
+    /// storage engine when the file is closed;
+    /// the database is closed and opened again, even if trim on close is
+    /// disabled, so it waits for pending writes and blocks writers meanwhile;
+    /// `IWKV_TRUNC` is not applied again, docs are kept
+    pub fn compact(self) -> Result<Self> {
+        let oflags = self.ejdb_opts.kv.oflags & !DatabaseOpenMode::IWKV_TRUNC.bits();
+        let no_trim = DatabaseOpenMode::IWKV_NO_TRIM_ON_CLOSE.bits();
+        if oflags & no_trim == 0 {
+            return self.reopen_with(oflags);
+        }
+        self.reopen_with(oflags & !no_trim)?.reopen_with(oflags)
+    }
+
+    /// enter read scope; nested read scopes are allowed,
+    /// so are reads within a write scope
+    #[inline]
+    pub(crate) fn enter_read(&self) -> TxnGuard<'_> {
+        let prev = self.txn.get();
+        let next = match prev {
+            TxnState::Idle => TxnState::Read(1),
+            TxnState::Read(n) => TxnState::Read(n + 1),
+            TxnState::Write { reading } => TxnState::Write {
+                reading: reading + 1,
+            },
+        };
+        self.txn.set(next);
+        TxnGuard { db: self, prev }
+    }
+
+    /// enter write scope; fails if already in any scope
+    #[inline]
+    pub(crate) fn enter_write(&self) -> Result<TxnGuard<'_>> {
+        let prev = self.txn.get();
+        match prev {
+            TxnState::Idle => {
+                self.txn.set(TxnState::Write { reading: 0 });
+                Ok(TxnGuard { db: self, prev })
+            }
+            TxnState::Read(_) => Err(EjdbError::TxnConflict("write scope within read scope")),
+            TxnState::Write { .. } => Err(EjdbError::TxnConflict("nested write scope")),
+        }
+    }
+
+    /// fails if within read scope, or while a query is iterating
+    /// within write scope
+    #[inline(always)]
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        match self.txn.get() {
+            TxnState::Read(_) => Err(EjdbError::TxnConflict("write within read scope")),
+            // e.g. put from a query visitor within write scope
+            TxnState::Write { reading } if reading > 0 => {
+                Err(EjdbError::TxnConflict("write within read scope"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// run closure within read scope, any write in the scope fails
+    /// with `EjdbError::TxnConflict`
+    #[inline]
+    pub fn read_txn<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Database) -> Result<R>,
+    {
+        let _guard = self.enter_read();
+        (f)(self)
+    }
+
+    /// run closure within write scope;
+    /// fails with `EjdbError::TxnConflict` if called within another scope
+    #[inline]
+    pub fn write_txn<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Database) -> Result<R>,
+    {
+        let _guard = self.enter_write()?;
+        (f)(self)
+    }
+
+    /// run closure within write scope, joining the current one if any;
+    /// fails with `EjdbError::TxnConflict` within read scope
+    #[inline]
+    pub(crate) fn join_write_txn<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Database) -> Result<R>,
+    {
+        if let TxnState::Write { .. } = self.txn.get() {
+            self.check_writable()?;
+            (f)(self)
+        } else {
+            self.write_txn(f)
+        }
+    }
+
     /// remove index if existing
     #[inline]
     pub fn remove_index<'a, 'b>(
@@ -51,6 +434,7 @@ impl Database {
         path: impl Into<StringPtr<'b>>,
         mode: sys::ejdb_idx_mode_t,
     ) -> Result<()> {
+        self.check_writable()?;
         let coll = collection.into();
         let path = path.into();
         let rc =
@@ -66,6 +450,7 @@ impl Database {
         path: impl Into<StringPtr<'b>>,
         mode: sys::ejdb_idx_mode_t,
     ) -> Result<()> {
+        self.check_writable()?;
         let coll = collection.into();
         let path = path.into();
         let rc =
@@ -76,29 +461,40 @@ impl Database {
     /// create collection with given name if not existing
     #[inline]
     pub fn ensure_collection<'a>(&self, collection: impl Into<StringPtr<'a>>) -> Result<()> {
+        self.check_writable()?;
         let coll = collection.into();
         let rc = unsafe { sys::ejdb_ensure_collection(self.raw_ptr(), coll.as_ptr()) };
         check_rc(rc)
     }
 
-    /// rename collection
+    /// rename collection; fails with `EjdbError::CollectionNotFound` if
+    /// `old_name` does not exist, or `EjdbError::CollectionExists` if
+    /// `new_name` exists
     #[inline]
     pub fn rename_collection<'a, 'b>(
         &self,
         old_name: impl Into<StringPtr<'a>>,
         new_name: impl Into<StringPtr<'b>>,
     ) -> Result<()> {
+        self.check_writable()?;
         let old_name = old_name.into();
         let new_name = new_name.into();
         let rc = unsafe {
             sys::ejdb_rename_collection(self.raw_ptr(), old_name.as_ptr(), new_name.as_ptr())
         };
+        if rc == sys::ejdb_ecode_t::EJDB_ERROR_COLLECTION_NOT_FOUND as u64 {
+            return Err(EjdbError::CollectionNotFound(old_name.to_owned()));
+        }
+        if rc == sys::ejdb_ecode_t::EJDB_ERROR_TARGET_COLLECTION_EXISTS as u64 {
+            return Err(EjdbError::CollectionExists(new_name.to_owned()));
+        }
         check_rc(rc)
     }
 
     /// remove collection
     #[inline]
     pub fn remove_collection<'a>(&self, collection: impl Into<StringPtr<'a>>) -> Result<()> {
+        self.check_writable()?;
         let coll = collection.into();
         let rc = unsafe { sys::ejdb_remove_collection(self.raw_ptr(), coll.as_ptr()) };
         check_rc(rc)
@@ -117,6 +513,45 @@ impl Database {
         check_rc(rc).and(Ok(ts))
     }
 
+    /// perform online backup, check target file before backup:
+    /// fails with `EjdbError::IoError` if parent folder of target file not existing,
+    /// or target file exists but `overwrite` is false
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn online_backup_with<'a>(
+        &self,
+        target_file: impl Into<StringPtr<'a>>,
+        overwrite: bool,
+    ) -> Result<BackupInfo> {
+        use std::{fs, io, path::Path};
+        let target_file = target_file.into().to_owned();
+        let path = Path::new(target_file.as_str());
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                let e = io::Error::new(io::ErrorKind::NotFound, "backup folder not found");
+                return Err(e.into());
+            }
+        }
+        if !overwrite && path.exists() {
+            let e = io::Error::new(io::ErrorKind::AlreadyExists, "backup file already exists");
+            return Err(e.into());
+        }
+        let time = self.online_backup(&target_file)?;
+        let size = fs::metadata(path)?.len();
+        Ok(BackupInfo { time, size })
+    }
+
+    /// flush pending changes to disk;
+    /// with WAL enabled a savepoint is created, otherwise data file is synced
+    #[inline]
+    pub fn sync(&self) -> Result<()> {
+        let mut kv = ptr::null_mut();
+        let rc = unsafe { sys::ejdb_get_iwkv(self.raw_ptr(), &mut kv) };
+        check_rc(rc)?;
+        let rc = unsafe { sys::iwkv_sync(kv, 0) };
+        check_rc(rc)
+    }
+
     /// retrieve document by specified id
     #[inline]
     pub fn get<'a>(&self, collection: impl Into<StringPtr<'a>>, id: i64) -> Result<JBL> {
@@ -127,6 +562,27 @@ impl Database {
         Ok(JBL::from_ptr(jblp))
     }
 
+    /// get document by specified id, `None` if document or collection not found
+    #[inline]
+    pub fn get_opt<'a>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        id: i64,
+    ) -> Result<Option<JBL>> {
+        match self.get(collection, id) {
+            Ok(jbl) => Ok(Some(jbl)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// check if document exists by specified id;
+    /// Note: document is still loaded, as EJDB2 has no cheaper way
+    #[inline]
+    pub fn exists<'a>(&self, collection: impl Into<StringPtr<'a>>, id: i64) -> Result<bool> {
+        self.get_opt(collection, id).map(|v| v.is_some())
+    }
+
     /// save document under specified id,
     /// or insert new document if id not specified
     #[inline]
@@ -136,8 +592,36 @@ impl Database {
         json: impl Into<StringPtr<'b>>,
         id: Option<i64>,
     ) -> Result<i64> {
+        self.check_writable()?;
         let jbl = JBL::from_json(json)?;
-        let coll = collection.into();
+        self.put_jbl(collection, &jbl, id)
+    }
+
+    /// save JBL document under specified id,
+    /// or insert new document if id not specified
+    #[inline]
+    pub fn put_jbl<'a>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        jbl: &JBL,
+        id: Option<i64>,
+    ) -> Result<i64> {
+        self.check_writable()?;
+        #[cfg(feature = "std")]
+        {
+            if let Some(field) = &self.mtime_field {
+                if jbl.value_type() == JsonType::Object {
+                    let mut doc = jbl.try_clone()?;
+                    doc.merge_jbl(&mtime_patch(field)?)?;
+                    return self.put_raw(collection.into(), &doc, id);
+                }
+            }
+        }
+        self.put_raw(collection.into(), jbl, id)
+    }
+
+    #[inline]
+    fn put_raw(&self, coll: StringPtr<'_>, jbl: &JBL, id: Option<i64>) -> Result<i64> {
         let mut ret_id = 0_i64;
         let rc = match id {
             Some(id) => {
@@ -152,6 +636,106 @@ impl Database {
         check_rc(rc).and(Ok(ret_id))
     }
 
+    /// insert docs with generated ids as one batch and return ids in order;
+    /// the batch runs in write scope, joining the current one if any,
+    /// and ends with `sync`, so docs are durable once it returns.
+    /// Unlike `Collection::put_many`, which is a plain loop of inserts, it
+    /// fails at once with `EjdbError::TxnConflict` within read scope.
+    /// Note: WAL savepoints by timer still run during the load, EJDB2 has
+    /// no API to suspend them, so throughput is that of a loop of `put`;
+    /// EJDB2 has no multi-document transactions: on failure docs inserted
+    /// before are kept but not synced
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn put_batch<'a, 'b, I, T>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        docs: I,
+    ) -> Result<Vec<i64>>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<StringPtr<'b>>,
+    {
+        let coll = collection.into().to_owned();
+        let put_all = |db: &Database| -> Result<Vec<i64>> {
+            let ids = docs
+                .into_iter()
+                .map(|doc| db.put(&coll, doc, None))
+                .collect::<Result<Vec<_>>>()?;
+            db.sync()?;
+            Ok(ids)
+        };
+        self.join_write_txn(put_all)
+    }
+
+    /// insert each line of newline-delimited JSON as new document and
+    /// return the number inserted, blank lines are skipped; fails with
+    /// `EjdbError::ImportError` at the first bad line, lines before are kept
+    #[cfg(feature = "std")]
+    pub fn import_ndjson<'a, R: std::io::Read>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        reader: R,
+    ) -> Result<usize> {
+        use std::io::BufRead;
+        let coll = collection.into().to_owned();
+        let mut count = 0;
+        for (i, line) in std::io::BufReader::new(reader).lines().enumerate() {
+            let res = line.map_err(EjdbError::from).and_then(|line| {
+                if line.trim().is_empty() {
+                    return Ok(false);
+                }
+                self.put(&coll, line, None).and(Ok(true))
+            });
+            match res {
+                Ok(true) => count += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    return Err(EjdbError::ImportError {
+                        line: i + 1,
+                        error: Box::new(e),
+                    })
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// write all documents of collection as newline-delimited JSON,
+    /// one compact document per line, and return the number written;
+    /// documents are streamed while visited, ids are not included
+    #[cfg(feature = "std")]
+    pub fn export_ndjson<'a, W: std::io::Write>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        w: &mut W,
+    ) -> Result<usize> {
+        let query = self.query_with_collection("/*", collection)?;
+        query.fold(0, |n, doc| {
+            doc.print(&mut *w, Some(JsonPrintFlags::COMPACT_RAW))?;
+            w.write_all(b"\n")?;
+            Ok(n + 1)
+        })
+    }
+
+    /// same as `export_ndjson`, but the document id is stored in `id_field`
+    /// of each exported document, which replaces a property of same name
+    #[cfg(feature = "std")]
+    pub fn export_ndjson_with_id<'a, W: std::io::Write>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        w: &mut W,
+        id_field: &str,
+    ) -> Result<usize> {
+        let query = self.query_with_collection("/*", collection)?;
+        query.fold(0, |n, doc| {
+            let mut jbl = doc.to_jbl()?;
+            jbl.set_prop(id_field, doc.id())?;
+            jbl.print(&mut *w, Some(JsonPrintFlags::COMPACT_RAW))?;
+            w.write_all(b"\n")?;
+            Ok(n + 1)
+        })
+    }
+
     /// apply JSON patch to document identified by id
     #[inline]
     pub fn patch<'a, 'b>(
@@ -160,12 +744,47 @@ impl Database {
         json: impl Into<StringPtr<'b>>,
         id: i64,
     ) -> Result<()> {
+        self.check_writable()?;
         let coll = collection.into();
+        #[cfg(feature = "std")]
+        {
+            if let Some(field) = &self.mtime_field {
+                let patch = stamp_patch(JBL::from_json(json)?, field)?;
+                let rc = unsafe {
+                    sys::ejdb_patch_jbl(self.raw_ptr(), coll.as_ptr(), patch.raw_ptr(), id)
+                };
+                return check_rc(rc);
+            }
+        }
         let json = json.into();
         let rc = unsafe { sys::ejdb_patch(self.raw_ptr(), coll.as_ptr(), json.as_ptr(), id) };
         check_rc(rc)
     }
 
+    /// apply parsed patch to document identified by id
+    #[inline]
+    pub(crate) fn patch_jbl<'a>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        patch: &JBL,
+        id: i64,
+    ) -> Result<()> {
+        self.check_writable()?;
+        let coll = collection.into();
+        #[cfg(feature = "std")]
+        {
+            if let Some(field) = &self.mtime_field {
+                let patch = stamp_patch(patch.try_clone()?, field)?;
+                let rc = unsafe {
+                    sys::ejdb_patch_jbl(self.raw_ptr(), coll.as_ptr(), patch.raw_ptr(), id)
+                };
+                return check_rc(rc);
+            }
+        }
+        let rc = unsafe { sys::ejdb_patch_jbl(self.raw_ptr(), coll.as_ptr(), patch.raw_ptr(), id) };
+        check_rc(rc)
+    }
+
     /// apply JSON merge patch to document identified by id
     /// or insert new document under specified id
     #[inline]
@@ -175,7 +794,18 @@ impl Database {
         json: impl Into<StringPtr<'b>>,
         id: i64,
     ) -> Result<()> {
+        self.check_writable()?;
         let coll = collection.into();
+        #[cfg(feature = "std")]
+        {
+            if let Some(field) = &self.mtime_field {
+                let patch = stamp_patch(JBL::from_json(json)?, field)?;
+                let rc = unsafe {
+                    sys::ejdb_merge_or_put_jbl(self.raw_ptr(), coll.as_ptr(), patch.raw_ptr(), id)
+                };
+                return check_rc(rc);
+            }
+        }
         let json = json.into();
         let rc =
             unsafe { sys::ejdb_merge_or_put(self.raw_ptr(), coll.as_ptr(), json.as_ptr(), id) };
@@ -185,11 +815,34 @@ impl Database {
     ///remove document identified by given id
     #[inline]
     pub fn del<'a>(&self, collection: impl Into<StringPtr<'a>>, id: i64) -> Result<()> {
+        self.check_writable()?;
         let coll = collection.into();
         let rc = unsafe { sys::ejdb_del(self.raw_ptr(), coll.as_ptr(), id) };
         check_rc(rc)
     }
 
+    /// check JSON can be parsed, without storing it;
+    /// fails with `EjdbError::InvalidJson` carrying the parse error code
+    #[inline]
+    pub fn validate_json<'a>(json: impl Into<StringPtr<'a>>) -> Result<()> {
+        JBL::from_json(json).and(Ok(()))
+    }
+
+    /// same as `validate_json`, but fails with `EjdbError::TooLarge`
+    /// without parsing if JSON is longer than `max_bytes`
+    #[inline]
+    pub fn validate_json_max<'a>(json: impl Into<StringPtr<'a>>, max_bytes: usize) -> Result<()> {
+        let json = json.into();
+        let size = unsafe { crate::ffi::strlen(json.as_ptr()) };
+        if size > max_bytes {
+            return Err(EjdbError::TooLarge {
+                size,
+                max: max_bytes,
+            });
+        }
+        Self::validate_json(json)
+    }
+
     /// return JSON document described database structure
     #[inline]
     pub fn get_meta(&self) -> Result<JBL> {
@@ -199,6 +852,73 @@ impl Database {
         Ok(JBL::from_ptr(jblp))
     }
 
+    /// names of all collections, without parsing indexes or stats
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn collection_names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        self.for_each_collection_name(|name| {
+            names.push(name.as_str().into());
+            Ok(())
+        })?;
+        Ok(names)
+    }
+
+    /// visit names of all collections read from meta
+    fn for_each_collection_name<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(XString) -> Result<()>,
+    {
+        let meta = self.get_meta()?;
+        let colls = match meta.get("collections") {
+            Ok(v) => v,
+            Err(e) if e.is_not_found() => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        colls.for_each(|_, coll| (f)(coll.get_str("name")?))
+    }
+
+    /// handles of all existing collections, enumerated from meta
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn collections(&self) -> Result<Vec<Collection<'_>>> {
+        let names = self.collection_names()?;
+        Ok(names
+            .into_iter()
+            .map(|name| Collection::new(self, name))
+            .collect())
+    }
+
+    /// collection whose name equals `name` ignoring case, or `name` as is
+    /// if none; an exact match is preferred, otherwise fails with
+    /// `EjdbError::AmbiguousCollection` if several names match.
+    /// Names are resolved by this crate from meta, EJDB2 itself is case-sensitive
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn collection_ci(&self, name: &str) -> Result<Collection<'_>> {
+        let names = self.collection_names()?;
+        if names.iter().any(|other| other == name) {
+            return Ok(Collection::new(self, name));
+        }
+        let lower = name.to_lowercase();
+        let mut found: Option<&str> = None;
+        for other in names.iter().filter(|other| other.to_lowercase() == lower) {
+            if found.is_some() {
+                return Err(EjdbError::AmbiguousCollection(name.into()));
+            }
+            found = Some(other);
+        }
+        Ok(Collection::new(self, found.unwrap_or(name)))
+    }
+
+    /// true if collection exists, read from meta
+    pub fn collection_exists<'a>(&self, name: impl Into<StringPtr<'a>>) -> Result<bool> {
+        let name = name.into().to_owned();
+        let mut found = false;
+        self.for_each_collection_name(|other| {
+            found = found || other.as_str() == name.as_str();
+            Ok(())
+        })?;
+        Ok(found)
+    }
+
     #[inline]
     pub fn collection<'db, 'a>(&'db self, name: impl Into<StringPtr<'a>>) -> Collection<'db> {
         Collection::new(self, name)
@@ -208,6 +928,24 @@ impl Database {
         let jql = JQL::create(jql)?;
         Ok(Query::new(jql, self))
     }
+    /// run query and map all matched docs, see [`Query::to_vec`];
+    /// use `query` to configure skip, limit or log
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn find_all<'a, F, T>(&self, jql: impl Into<StringPtr<'a>>, f: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&JsonDoc) -> Result<T>,
+    {
+        self.query(jql)?.to_vec(f)
+    }
+    /// run query and map the first matched doc, see [`Query::first`]
+    #[inline]
+    pub fn find_first<'a, F, T>(&self, jql: impl Into<StringPtr<'a>>, f: F) -> Result<Option<T>>
+    where
+        F: FnMut(&JsonDoc) -> Result<T>,
+    {
+        self.query(jql)?.first(f)
+    }
     #[inline]
     pub fn query_with_collection<'a, 'b, 'c>(
         &'a self,
@@ -219,68 +957,367 @@ impl Database {
     }
 }
 
+/// merge patch `{field: <epoch millis>}`
+#[cfg(feature = "std")]
+fn mtime_patch(field: &XString) -> Result<JBL> {
+    let mut patch = JBL::new_object()?;
+    patch.set_prop(field, now_millis())?;
+    Ok(patch)
+}
+
+/// add mtime field to JSON patch or JSON merge patch
+#[cfg(feature = "std")]
+fn stamp_patch(mut patch: JBL, field: &XString) -> Result<JBL> {
+    if patch.value_type() != JsonType::Array {
+        patch.merge_jbl(&mtime_patch(field)?)?;
+        return Ok(patch);
+    }
+    // append `add` operation to JSON patch
+    let path = jbl::key_pointer(field.as_str());
+    let mut op = JBL::object_builder()?;
+    op.prop("op", "add")?
+        .prop("path", path.as_str())?
+        .prop("value", now_millis())?;
+    let mut append = JBL::object_builder()?;
+    append
+        .prop("op", "add")?
+        .prop("path", "/-")?
+        .prop("value", op.build())?;
+    patch.patch_jbl(&JBL::from_values(vec![append.build()])?)?;
+    Ok(patch)
+}
+
+#[cfg(feature = "std")]
+fn now_millis() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
 impl Drop for Database {
     #[inline(always)]
     fn drop(&mut self) {
+        // already closed by `close` or failed reopen
+        if self.ptr.is_null() {
+            return;
+        }
         let rc = unsafe { sys::ejdb_close(&mut self.ptr) };
-        debug_assert!(rc == 0);
+        if let Err(e) = check_rc(rc) {
+            report_close_error(&e);
+        }
     }
 }
 
-pub struct Collection<'db> {
-    db: &'db Database,
-    name: XString,
-}
+// the EJDB2 handle is not bound to the thread which opened it,
+// other fields are owned values
+unsafe impl Send for Database {}
 
-impl<'db> Collection<'db> {
+/// database shared between threads, operations are serialized by a mutex
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct SharedDatabase(std::sync::Arc<std::sync::Mutex<Database>>);
+
+#[cfg(feature = "std")]
+impl SharedDatabase {
     #[inline]
-    pub(crate) fn new<'a>(db: &'db Database, name: impl Into<StringPtr<'a>>) -> Self {
-        Self {
-            db,
-            name: name.into().to_owned(),
-        }
+    pub fn new(db: Database) -> Self {
+        Self(std::sync::Arc::new(std::sync::Mutex::new(db)))
     }
 
-    /// get collection name
+    /// lock database for exclusive use by current thread; a panic of
+    /// another thread does not poison it, as EJDB2 state stays consistent
     #[inline]
-    pub fn name(&self) -> &XString {
-        &self.name
+    pub fn lock(&self) -> std::sync::MutexGuard<'_, Database> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
     }
-    /// rename collection
+
+    /// run `f` with database locked
     #[inline]
-    pub fn rename<'a>(&mut self, name: impl Into<StringPtr<'a>>) -> Result<&mut Self> {
-        let name = name.into().to_owned();
-        let res = self.db.rename_collection(self.name(), &name);
-        if res.is_ok() {
-            self.name = name;
-        }
-        res.and(Ok(self))
+    pub fn with<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Database) -> Result<T>,
+    {
+        f(&self.lock())
     }
+}
 
-    /// create index with given parameters if not existing
+#[cfg(feature = "std")]
+impl From<Database> for SharedDatabase {
     #[inline]
-    pub fn ensure_index<'a>(
-        &self,
-        path: impl Into<StringPtr<'a>>,
-        mode: sys::ejdb_idx_mode_t,
-    ) -> Result<()> {
-        self.db.ensure_index(self.name(), path, mode)
+    fn from(db: Database) -> Self {
+        Self::new(db)
     }
-    /// remove index if existing
+}
+
+/// database opened with `IWKV_RDONLY` by [`EJDB2Builder::build_read_only`],
+/// which only exposes reads, so writes are rejected at compile time;
+/// queries with `apply` or `del` are still rejected by storage at runtime
+///
+/// [`EJDB2Builder::build_read_only`]: crate::EJDB2Builder::build_read_only
+pub struct ReadOnlyDatabase(pub(crate) Database);
+
+impl ReadOnlyDatabase {
+    /// path of database file
     #[inline]
-    pub fn remove_index<'a>(
-        &self,
-        path: impl Into<StringPtr<'a>>,
+    pub fn path(&self) -> &str {
+        self.0.path()
+    }
+    /// retrieve document by specified id
+    #[inline]
+    pub fn get<'a>(&self, collection: impl Into<StringPtr<'a>>, id: i64) -> Result<JBL> {
+        self.0.get(collection, id)
+    }
+    /// get document by specified id, `None` if document or collection not found
+    #[inline]
+    pub fn get_opt<'a>(
+        &self,
+        collection: impl Into<StringPtr<'a>>,
+        id: i64,
+    ) -> Result<Option<JBL>> {
+        self.0.get_opt(collection, id)
+    }
+    /// check if document exists by specified id
+    #[inline]
+    pub fn exists<'a>(&self, collection: impl Into<StringPtr<'a>>, id: i64) -> Result<bool> {
+        self.0.exists(collection, id)
+    }
+    /// number of docs in collection
+    #[inline]
+    pub fn count<'a>(&self, collection: impl Into<StringPtr<'a>>) -> Result<usize> {
+        self.0.query_with_collection("/*", collection)?.count_fast()
+    }
+    #[inline]
+    pub fn query<'a, 'b>(&'a self, jql: impl Into<StringPtr<'b>>) -> Result<Query<'a>> {
+        self.0.query(jql)
+    }
+    #[inline]
+    pub fn query_with_collection<'a, 'b, 'c>(
+        &'a self,
+        jql: impl Into<StringPtr<'b>>,
+        collection: impl Into<StringPtr<'c>>,
+    ) -> Result<Query<'a>> {
+        self.0.query_with_collection(jql, collection)
+    }
+    /// return JSON document described database structure
+    #[inline]
+    pub fn get_meta(&self) -> Result<JBL> {
+        self.0.get_meta()
+    }
+    /// names of all collections
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn collection_names(&self) -> Result<Vec<String>> {
+        self.0.collection_names()
+    }
+    /// close database and return error if failed
+    #[inline]
+    pub fn close(self) -> Result<()> {
+        self.0.close()
+    }
+}
+
+pub struct Collection<'db> {
+    db: &'db Database,
+    name: XString,
+}
+
+impl<'db> Collection<'db> {
+    #[inline]
+    pub(crate) fn new<'a>(db: &'db Database, name: impl Into<StringPtr<'a>>) -> Self {
+        Self {
+            db,
+            name: name.into().to_owned(),
+        }
+    }
+
+    /// get collection name
+    #[inline]
+    pub fn name(&self) -> &XString {
+        &self.name
+    }
+    /// rename collection, see [`Database::rename_collection`] for errors
+    #[inline]
+    pub fn rename<'a>(&mut self, name: impl Into<StringPtr<'a>>) -> Result<&mut Self> {
+        let name = name.into().to_owned();
+        let res = self.db.rename_collection(self.name(), &name);
+        if res.is_ok() {
+            self.name = name;
+        }
+        res.and(Ok(self))
+    }
+
+    /// create index with given parameters if not existing;
+    /// `mode` is a combination of `IndexMode` bits
+    #[inline]
+    pub fn ensure_index<'a>(
+        &self,
+        path: impl Into<StringPtr<'a>>,
+        mode: sys::ejdb_idx_mode_t,
+    ) -> Result<()> {
+        self.db.ensure_index(self.name(), path, mode)
+    }
+    /// create index of string values if not existing
+    #[inline]
+    pub fn ensure_str_index<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<()> {
+        self.ensure_index(path, IndexMode::STR.bits())
+    }
+    /// create unique index of string values if not existing
+    #[inline]
+    pub fn ensure_unique_str_index<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<()> {
+        self.ensure_index(path, (IndexMode::STR | IndexMode::UNIQUE).bits())
+    }
+    /// create index of integer values if not existing
+    #[inline]
+    pub fn ensure_i64_index<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<()> {
+        self.ensure_index(path, IndexMode::I64.bits())
+    }
+    /// create index of floating point values if not existing
+    #[inline]
+    pub fn ensure_f64_index<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<()> {
+        self.ensure_index(path, IndexMode::F64.bits())
+    }
+    /// remove index if existing
+    #[inline]
+    pub fn remove_index<'a>(
+        &self,
+        path: impl Into<StringPtr<'a>>,
         mode: sys::ejdb_idx_mode_t,
     ) -> Result<()> {
         self.db.remove_index(self.name(), path, mode)
     }
+    /// indexes of collection parsed from meta,
+    /// empty if collection does not exist
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn indexes(&self) -> Result<Vec<IndexInfo>> {
+        let meta = self.db.get_meta()?;
+        let mut indexes = Vec::new();
+        let colls = match meta.get("collections") {
+            Ok(v) => v,
+            Err(e) if e.is_not_found() => return Ok(indexes),
+            Err(e) => return Err(e),
+        };
+        colls.for_each(|_, coll| {
+            if coll.get_str("name")?.as_str() != self.name.as_str() {
+                return Ok(());
+            }
+            let items = match coll.get("indexes") {
+                Ok(v) => v,
+                Err(e) if e.is_not_found() => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            items.for_each(|_, idx| {
+                let rnum = match idx.get_i64("rnum") {
+                    Ok(v) => Some(v),
+                    Err(e) if e.is_not_found() => None,
+                    Err(e) => return Err(e),
+                };
+                indexes.push(IndexInfo {
+                    ptr: idx.get_str("ptr")?.as_str().into(),
+                    mode: IndexMode::from_bits_truncate(idx.get_i64("mode")? as u8),
+                    idbf: idx.get_i64("idbf")?,
+                    rnum,
+                });
+                Ok(())
+            })
+        })?;
+        Ok(indexes)
+    }
     /// create collection with given name if not existing
     #[inline]
     pub fn ensure_collection(&self) -> Result<()> {
         self.db.ensure_collection(self.name())
     }
 
+    /// delete all docs and return the number deleted;
+    /// unlike `remove`, the collection and its indexes are kept
+    #[inline]
+    pub fn clear(&self) -> Result<usize> {
+        self.db
+            .query_with_collection("/* | del", self.name())?
+            .count_written()
+    }
+
+    /// delete docs whose numeric property `field` is less than `value`,
+    /// e.g. expired docs by timestamp; return the number deleted.
+    /// `field` is a single property name quoted in query, not a path;
+    /// docs without the property are kept
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn delete_where_lt(&self, field: &str, value: i64) -> Result<usize> {
+        let mut jql = String::from("/[\"");
+        jql.push_str(&crate::jql::escape_literal(field));
+        jql.push_str("\" < :value] | del");
+        let mut query = self.db.query_with_collection(jql, self.name())?;
+        query.jql().set_i64("value", value)?;
+        query.count_written()
+    }
+
+    /// count distinct values at rfc6901 `path` over all docs,
+    /// docs without the path are skipped; values are compared by their
+    /// compact JSON, so `1` and `1.0` are different values.
+    /// scans the whole collection and keeps every distinct value in memory,
+    /// which is O(n) in the number of distinct values
+    #[cfg(feature = "std")]
+    pub fn distinct_count(&self, path: &str) -> Result<usize> {
+        let mut n = 0;
+        self.for_each_distinct(path, |_, _| {
+            n += 1;
+            Ok(())
+        })?;
+        Ok(n)
+    }
+
+    /// distinct values at rfc6901 `path` in order of first occurrence,
+    /// see `distinct_count` for comparison and memory cost;
+    /// arrays and objects are returned as `JBLValue::Nested`
+    #[cfg(feature = "std")]
+    pub fn distinct_values(&self, path: &str) -> Result<Vec<jbl::JBLValue<'static>>> {
+        use jbl::JBLValue;
+        let mut values = Vec::new();
+        self.for_each_distinct(path, |v, json| {
+            let value = match v.value_type() {
+                JsonType::None | JsonType::Null => JBLValue::Null,
+                JsonType::Boolean => JBLValue::Boolean(v.as_i64() != 0),
+                JsonType::Integer => JBLValue::Integer(v.as_i64()),
+                JsonType::Float => JBLValue::Float(v.as_f64()),
+                JsonType::String => JBLValue::String(StringPtr::XString(v.try_as_str()?.into())),
+                // copy, since the found value refers to the data of its doc
+                JsonType::Array | JsonType::Object => {
+                    JBLValue::Nested(JBL::from_json(&XString::from(json))?)
+                }
+            };
+            values.push(value);
+            Ok(())
+        })?;
+        Ok(values)
+    }
+
+    /// visit each distinct value at `path` with its compact JSON
+    #[cfg(feature = "std")]
+    fn for_each_distinct<F>(&self, path: &str, mut f: F) -> Result<()>
+    where
+        F: FnMut(&JBL, &[u8]) -> Result<()>,
+    {
+        let path = XString::from(path);
+        let mut seen = std::collections::HashSet::new();
+        let mut buf = XString::new();
+        self.db
+            .query_with_collection("/*", self.name())?
+            .for_each(|doc| {
+                let doc = doc.to_jbl()?;
+                if let Some(v) = doc.find_opt(&path)? {
+                    v.write_json_into(&mut buf, Some(JsonPrintFlags::COMPACT_RAW))?;
+                    if !seen.contains(buf.to_bytes()) {
+                        let json = buf.to_bytes().to_vec();
+                        f(&v, &json)?;
+                        seen.insert(json);
+                    }
+                }
+                Ok(())
+            })
+    }
+
     /// remove collection
     #[inline]
     pub fn remove(self) -> core::result::Result<(), CollectionRemoveError<'db>> {
@@ -295,18 +1332,126 @@ impl<'db> Collection<'db> {
     pub fn get(&self, id: i64) -> Result<JBL> {
         self.db.get(self.name(), id)
     }
+    /// get document by specified id, `None` if not found
+    #[inline]
+    pub fn get_opt(&self, id: i64) -> Result<Option<JBL>> {
+        self.db.get_opt(self.name(), id)
+    }
+    /// get documents by ids in the same order, `None` for missing ids;
+    /// the collection name is passed to EJDB2 as is, without conversion
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn get_many(&self, ids: &[i64]) -> Result<Vec<Option<JBL>>> {
+        ids.iter()
+            .map(|&id| self.db.get_opt(&self.name, id))
+            .collect()
+    }
+    /// get document with its token for optimistic concurrency,
+    /// `None` if not found; the token is a 64-bit FNV-1a hash of the
+    /// compact JSON of the document, so it changes with any content change,
+    /// but not with writes restoring the same content
+    #[inline]
+    pub fn get_with_token(&self, id: i64) -> Result<Option<(JBL, u64)>> {
+        match self.get_opt(id)? {
+            Some(doc) => {
+                let token = doc_token(&doc)?;
+                Ok(Some((doc, token)))
+            }
+            None => Ok(None),
+        }
+    }
+    /// save document under id only if its current token equals `token`
+    /// from `get_with_token`, otherwise fails with `EjdbError::Conflict`,
+    /// also if the document is removed meanwhile; the check and the write
+    /// are not atomic against other processes or handles of the same file
+    pub fn put_if_token<'a>(
+        &self,
+        json: impl Into<StringPtr<'a>>,
+        id: i64,
+        token: u64,
+    ) -> Result<()> {
+        self.db.check_writable()?;
+        let current = match self.get_opt(id)? {
+            Some(doc) => doc_token(&doc)?,
+            None => return Err(EjdbError::Conflict(id)),
+        };
+        if current != token {
+            return Err(EjdbError::Conflict(id));
+        }
+        self.put(json, Some(id)).and(Ok(()))
+    }
+    /// check if document exists by specified id
+    #[inline]
+    pub fn exists(&self, id: i64) -> Result<bool> {
+        self.db.exists(self.name(), id)
+    }
     /// save document under specified id
     /// or insert new document if id not specified
     #[inline]
     pub fn put<'a>(&self, json: impl Into<StringPtr<'a>>, id: Option<i64>) -> Result<i64> {
         self.db.put(self.name(), json, id)
     }
+    /// save JBL document under specified id
+    /// or insert new document if id not specified
+    #[inline]
+    pub fn put_jbl(&self, jbl: &JBL, id: Option<i64>) -> Result<i64> {
+        self.db.put_jbl(self.name(), jbl, id)
+    }
+    /// insert new document with generated id
+    #[inline]
+    pub fn insert<'a>(&self, json: impl Into<StringPtr<'a>>) -> Result<i64> {
+        self.db.put(self.name(), json, None)
+    }
+    /// insert docs with generated ids and return ids in order;
+    /// stops at first error, docs inserted before are kept;
+    /// see `put_batch` to sync once all docs are inserted
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn put_many<'a, I, T>(&self, docs: I) -> Result<Vec<i64>>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<StringPtr<'a>>,
+    {
+        docs.into_iter().map(|doc| self.insert(doc)).collect()
+    }
+    /// insert docs with generated ids as one batch, see [`Database::put_batch`]
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn put_batch<'a, I, T>(&self, docs: I) -> Result<Vec<i64>>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<StringPtr<'a>>,
+    {
+        self.db.put_batch(self.name(), docs)
+    }
+    /// save document under specified id, replace if existing
+    #[inline]
+    pub fn upsert<'a>(&self, json: impl Into<StringPtr<'a>>, id: i64) -> Result<()> {
+        self.db.put(self.name(), json, Some(id)).and(Ok(()))
+    }
 
     /// apply JSON patch to document identified by id
     #[inline]
     pub fn patch<'a>(&self, json: impl Into<StringPtr<'a>>, id: i64) -> Result<()> {
         self.db.patch(self.name(), json, id)
     }
+    /// apply the same patch to documents by ids in order, the patch is
+    /// parsed once; returns number of patched docs, or
+    /// `EjdbError::BatchError` with the failed id, docs patched before are kept
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn patch_many<'a>(&self, json: impl Into<StringPtr<'a>>, ids: &[i64]) -> Result<usize> {
+        self.db.check_writable()?;
+        let patch = JBL::from_json(json)?;
+        for (done, &id) in ids.iter().enumerate() {
+            self.db
+                .patch_jbl(&self.name, &patch, id)
+                .map_err(|e| EjdbError::BatchError {
+                    id,
+                    done,
+                    error: Box::new(e),
+                })?;
+        }
+        Ok(ids.len())
+    }
     /// apply JSON merge patch to document identified by id
     /// or insert new document under specified id
     #[inline]
@@ -321,6 +1466,42 @@ impl<'db> Collection<'db> {
     }
 }
 
+/// FNV-1a hash of compact JSON of document
+fn doc_token(doc: &JBL) -> Result<u64> {
+    let mut buf = XString::new_with_size(doc.size() * 2);
+    doc.write_json_into(&mut buf, Some(JsonPrintFlags::COMPACT_RAW))?;
+    let hash = buf
+        .to_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    Ok(hash)
+}
+
+/// result of online backup
+#[derive(Debug, Clone, Copy)]
+pub struct BackupInfo {
+    /// backup finish time in milliseconds since epoch
+    pub time: u64,
+    /// backup file size in bytes
+    pub size: u64,
+}
+
+/// index definition read from meta, see [`Collection::indexes`]
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexInfo {
+    /// rfc6901 JSON pointer of indexed field
+    pub ptr: String,
+    /// value type and uniqueness
+    pub mode: IndexMode,
+    /// flags of underlying iwkv database
+    pub idbf: i64,
+    /// number of indexed records, if reported
+    pub rnum: Option<i64>,
+}
+
 pub struct CollectionRemoveError<'a> {
     pub collection: Collection<'a>,
     pub error: EjdbError,
@@ -343,6 +1524,415 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_patch_many() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let coll = db.collection("c1");
+            assert_eq!(coll.patch_many("{\"d\":1}", &[1, 3])?, 2);
+            assert_eq!(coll.get(1)?.get_i64("d")?, 1);
+            assert_eq!(coll.get(3)?.get_i64("d")?, 1);
+            assert!(coll.get(2)?.find_opt("/d")?.is_none());
+            let res = coll.patch_many("{\"e\":1}", &[4, 100, 5]);
+            match res {
+                Err(EjdbError::BatchError { id, done, error }) => {
+                    assert_eq!((id, done), (100, 1));
+                    assert!(error.is_not_found());
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+            assert_eq!(coll.get(4)?.get_i64("e")?, 1);
+            assert!(coll.get(5)?.find_opt("/e")?.is_none());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_collections() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            db.collection("c2").ensure_collection()?;
+            let mut names: Vec<_> = db
+                .collections()?
+                .iter()
+                .map(|c| c.name().as_str().to_owned())
+                .collect();
+            names.sort();
+            assert_eq!(names, vec!["c1", "c2"]);
+            let counts = db
+                .collections()?
+                .iter()
+                .map(|c| db.query_with_collection("/*", c.name())?.count())
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(counts.iter().sum::<usize>(), 8);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_delete_where_lt() {
+        catch(|| {
+            let db = TestDb::new();
+            let coll = db.collection("cache");
+            for exp in &[10, 20, 30] {
+                coll.insert(format!("{{\"expires at\":{}}}", exp))?;
+            }
+            coll.insert("{\"x\":1}")?;
+            assert_eq!(coll.delete_where_lt("expires at", 25)?, 2);
+            let left = db.query_with_collection("/*", "cache")?.count()?;
+            assert_eq!(left, 2);
+            assert_eq!(coll.delete_where_lt("expires at", 25)?, 0);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_distinct() {
+        catch(|| {
+            let db = TestDb::new();
+            let coll = db.collection("tags");
+            for json in &[
+                r#"{"t":"a"}"#,
+                r#"{"t":"b"}"#,
+                r#"{"t":"a"}"#,
+                r#"{"t":1}"#,
+                r#"{"x":1}"#,
+                r#"{"t":[1]}"#,
+                r#"{"t":[1]}"#,
+            ] {
+                coll.insert(*json)?;
+            }
+            assert_eq!(coll.distinct_count("/t")?, 4);
+            assert_eq!(coll.distinct_count("/y")?, 0);
+            let values = coll.distinct_values("/t")?;
+            assert_eq!(values.len(), 4);
+            match (&values[0], &values[1], &values[2], &values[3]) {
+                (
+                    jbl::JBLValue::String(StringPtr::XString(a)),
+                    jbl::JBLValue::String(StringPtr::XString(b)),
+                    jbl::JBLValue::Integer(1),
+                    jbl::JBLValue::Nested(arr),
+                ) => {
+                    assert_eq!(a.to_bytes(), b"a");
+                    assert_eq!(b.to_bytes(), b"b");
+                    let json: String = arr.as_json(None)?;
+                    assert_eq!(json, "[1]");
+                }
+                _ => panic!("unexpected distinct values"),
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_find_all() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let ids = db.find_all("@c1/[c > 3]", |doc| Ok(doc.id()))?;
+            assert_eq!(ids.len(), 3);
+            let json: Option<String> = db.find_first("@c1/=1", |doc| doc.as_json(None))?;
+            assert!(json.unwrap().contains("abc1"));
+            assert!(db.find_first("@c1/=100", |doc| Ok(doc.id()))?.is_none());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_collection_ci() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            db.collection("Users").ensure_collection()?;
+            assert_eq!(db.collection_ci("users")?.name().as_str(), "Users");
+            assert_eq!(db.collection_ci("C1")?.name().as_str(), "c1");
+            assert_eq!(db.collection_ci("other")?.name().as_str(), "other");
+            db.collection("USERS").ensure_collection()?;
+            let res = db.collection_ci("users");
+            assert!(matches!(res, Err(EjdbError::AmbiguousCollection(_))));
+            assert_eq!(db.collection_ci("USERS")?.name().as_str(), "USERS");
+            // exact match listed after ambiguous ones
+            db.collection("users").ensure_collection()?;
+            assert_eq!(db.collection_ci("users")?.name().as_str(), "users");
+            assert!(db.collection_ci("uSers").is_err());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_validate_json() {
+        Database::validate_json("{\"a\":[1,2]}").unwrap();
+        let res = Database::validate_json("{\"a\":");
+        assert!(matches!(res, Err(EjdbError::InvalidJson(rc)) if rc != 0));
+        Database::validate_json_max("[1]", 3).unwrap();
+        let res = Database::validate_json_max("[1,2]", 3);
+        assert!(matches!(res, Err(EjdbError::TooLarge { size: 5, max: 3 })));
+        let res = Database::validate_json_max("[1", 3);
+        assert!(matches!(res, Err(EjdbError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_put_if_token() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let coll = db.collection("c1");
+            let (doc, token) = coll.get_with_token(1)?.unwrap();
+            assert_eq!(doc.get_str("a")?, "abc1");
+            assert_eq!(coll.get_with_token(1)?.unwrap().1, token);
+            coll.put_if_token("{\"a\":\"x\"}", 1, token)?;
+            let res = coll.put_if_token("{\"a\":\"y\"}", 1, token);
+            assert!(matches!(res, Err(EjdbError::Conflict(1))));
+            assert_eq!(coll.get(1)?.get_str("a")?, "x");
+            let res = coll.put_if_token("{}", 100, token);
+            assert!(matches!(res, Err(EjdbError::Conflict(100))));
+            assert!(coll.get_with_token(100)?.is_none());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_many() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let docs = db.collection("c1").get_many(&[3, 100, 1])?;
+            assert_eq!(docs.len(), 3);
+            assert_eq!(docs[0].as_ref().unwrap().get_str("a")?, "abc3");
+            assert!(docs[1].is_none());
+            assert_eq!(docs[2].as_ref().unwrap().get_str("a")?, "abc1");
+            assert!(db.collection("none").get_many(&[1])?[0].is_none());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_exists() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            assert!(db.collection("c1").exists(1)?);
+            assert!(!db.collection("c1").exists(100)?);
+            let res = db.collection("c1").get(100);
+            assert!(res.unwrap_err().is_not_found());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_collection_names() {
+        catch(|| {
+            let db = TestDb::new();
+            assert!(db.collection_names()?.is_empty());
+            db.ensure_collection("c1")?;
+            db.ensure_collection("c2")?;
+            let mut names = db.collection_names()?;
+            names.sort();
+            assert_eq!(names, vec!["c1", "c2"]);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_opt() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let jbl = db.collection("c1").get_opt(1)?;
+            assert_eq!(jbl.unwrap().get_str("b")?, "cde1");
+            assert!(db.collection("c1").get_opt(100)?.is_none());
+            assert!(db.get_opt("none", 1)?.is_none());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_indexes() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let coll = db.collection("c1");
+            assert!(coll.indexes()?.is_empty());
+            coll.ensure_unique_str_index("/b")?;
+            coll.ensure_i64_index("/c")?;
+            let mut indexes = coll.indexes()?;
+            indexes.sort_by(|a, b| a.ptr.cmp(&b.ptr));
+            assert_eq!(indexes.len(), 2);
+            assert_eq!(indexes[0].ptr, "/b");
+            assert_eq!(indexes[0].mode, IndexMode::STR | IndexMode::UNIQUE);
+            assert_eq!(indexes[1].ptr, "/c");
+            assert_eq!(indexes[1].mode, IndexMode::I64);
+            assert!(db.collection("none").indexes()?.is_empty());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_ensure_typed_index() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let coll = db.collection("c1");
+            coll.ensure_str_index("/a")?;
+            coll.ensure_unique_str_index("/b")?;
+            coll.ensure_i64_index("/c")?;
+            coll.ensure_f64_index("/d")?;
+            let meta = db.get_meta()?;
+            let indexes = meta.get("collections")?.at(0)?.get("indexes")?;
+            let mut modes = Vec::new();
+            indexes.for_each(|_, idx| {
+                let path = idx.get_str("ptr")?.as_str().to_owned();
+                modes.push((path, idx.get_i64("mode")?));
+                Ok(())
+            })?;
+            modes.sort();
+            let expected = vec![("/a", 0x04), ("/b", 0x05), ("/c", 0x08), ("/d", 0x10)];
+            let expected: Vec<_> = expected
+                .into_iter()
+                .map(|(p, m)| (p.to_owned(), m))
+                .collect();
+            assert_eq!(modes, expected);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_import_ndjson() {
+        catch(|| {
+            let db = TestDb::new();
+            let data = "{\"a\":1}\n\n  \n{\"a\":2}\r\n";
+            assert_eq!(db.import_ndjson("c1", data.as_bytes())?, 2);
+            assert_eq!(db.query("@c1/*")?.count()?, 2);
+            let data = "{\"a\":3}\n{\"a\":\n{\"a\":4}\n";
+            let res = db.import_ndjson("c1", data.as_bytes());
+            assert!(matches!(res, Err(EjdbError::ImportError { line: 2, .. })));
+            assert_eq!(db.query("@c1/*")?.count()?, 3);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_export_ndjson() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut buf = Vec::new();
+            assert_eq!(db.export_ndjson("c1", &mut buf)?, 8);
+            assert_eq!(buf.iter().filter(|&&c| c == b'\n').count(), 8);
+            assert_eq!(db.import_ndjson("c2", buf.as_slice())?, 8);
+            assert_eq!(db.query("@c2/*")?.count()?, 8);
+
+            let mut buf = Vec::new();
+            db.export_ndjson_with_id("c1", &mut buf, "_id")?;
+            let text = String::from_utf8(buf).unwrap();
+            let first: JBL = text.lines().next().unwrap().parse()?;
+            let id = first.get_i64("_id")?;
+            assert_eq!(db.get("c1", id)?.get_str("a")?, first.get_str("a")?);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_clear() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let coll = db.collection("c1");
+            coll.ensure_unique_str_index("/a")?;
+            assert_eq!(coll.clear()?, 8);
+            assert_eq!(db.query("@c1/*")?.count()?, 0);
+            assert_eq!(db.collection_names()?, vec!["c1".to_owned()]);
+            coll.put("{\"a\":\"abc1\"}", Some(1))?;
+            assert!(coll.put("{\"a\":\"abc1\"}", Some(2)).is_err());
+            assert_eq!(db.query("@c1/[a = \"abc1\"]")?.count()?, 1);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_clear_in_scope() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let coll = db.collection("c1");
+            let res = db.read_txn(|_| coll.clear());
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            let res = db.read_txn(|_| coll.delete_where_lt("c", 3));
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            assert_eq!(db.query("@c1/*")?.count()?, 8);
+            let deleted = db.write_txn(|_| Ok(coll.delete_where_lt("c", 3)? + coll.clear()?))?;
+            assert_eq!(deleted, 8);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_put_many() {
+        catch(|| {
+            let db = TestDb::new();
+            let coll = db.collection("c1");
+            let ids = coll.put_many(vec!["{\"a\":1}", "{\"a\":2}", "{\"a\":3}"])?;
+            assert_eq!(ids.len(), 3);
+            for (i, id) in ids.iter().enumerate() {
+                assert_eq!(coll.get(*id)?.get_i64("a")?, i as i64 + 1);
+            }
+            let res = coll.put_many(vec!["{\"a\":4}", "{", "{\"a\":5}"]);
+            assert!(res.is_err());
+            assert_eq!(db.query("@c1/*")?.count()?, 4);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_put_batch() {
+        catch(|| {
+            let db = TestDb::new();
+            let coll = db.collection("c1");
+            let ids = coll.put_batch(vec!["{\"a\":1}", "{\"a\":2}"])?;
+            assert_eq!(ids.len(), 2);
+            assert_eq!(coll.get(ids[1])?.get_i64("a")?, 2);
+            // joins enclosing write scope
+            let ids = db.write_txn(|db| db.put_batch("c1", vec!["{\"a\":3}"]))?;
+            assert_eq!(coll.get(ids[0])?.get_i64("a")?, 3);
+            let res = db.read_txn(|db| db.put_batch("c1", vec!["{\"a\":4}"]));
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    /// insert throughput of 100k docs with a loop of `put` and with batch,
+    /// which does the same inserts within write scope, so timings are
+    /// expected to be close; run by `cargo test -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_put_batch() {
+        use std::time::Instant;
+        const N: usize = 100_000;
+        let docs: Vec<String> = (0..N).map(|i| format!("{{\"n\":{}}}", i)).collect();
+        let db = TestDb::new();
+
+        let start = Instant::now();
+        for doc in docs.iter() {
+            db.put("c1", doc.as_str(), None).unwrap();
+        }
+        db.sync().unwrap();
+        let single = start.elapsed();
+
+        let start = Instant::now();
+        db.put_batch("c2", docs.iter().map(|d| d.as_str())).unwrap();
+        let batch = start.elapsed();
+
+        println!("{} docs: put {:?}, put_batch {:?}", N, single, batch);
+    }
+
     #[test]
     fn test_rename() {
         catch(|| {
@@ -352,4 +1942,349 @@ mod test {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_rename_errors() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            db.ensure_collection("c2")?;
+            assert!(db.collection_exists("c1")?);
+            assert!(!db.collection_exists("c3")?);
+            let mut coll = db.collection("c3");
+            let res = coll.rename("c4");
+            assert!(
+                matches!(res, Err(EjdbError::CollectionNotFound(ref name)) if name.as_str() == "c3")
+            );
+            let mut coll = db.collection("c1");
+            let res = coll.rename("c2");
+            assert!(
+                matches!(res, Err(EjdbError::CollectionExists(ref name)) if name.as_str() == "c2")
+            );
+            assert_eq!(coll.name().as_str(), "c1");
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_insert() {
+        catch(|| {
+            let db = TestDb::new();
+            let col = db.collection("c2");
+            let id1 = col.insert("{\"a\":1}")?;
+            let id2 = col.insert("{\"a\":2}")?;
+            let id3 = col.insert("{\"a\":3}")?;
+            assert_eq!(id2, id1 + 1);
+            assert_eq!(id3, id2 + 1);
+            col.upsert("{\"a\":4}", id2)?;
+            assert_eq!(col.get(id2)?.get_i64("a")?, 4);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_put_jbl() {
+        catch(|| {
+            let db = TestDb::new();
+            let col = db.collection("c2");
+            let mut jbl = JBL::new_object()?;
+            jbl.set_prop("a", "x\"y")?;
+            let id = col.put_jbl(&jbl, None)?;
+            assert_eq!(col.get(id)?.get_str("a")?, "x\"y");
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_online_backup() {
+        catch(|| {
+            let file = tmp_file("backup");
+            {
+                let db = TestDb::new_with_seed()?;
+                let info = db.online_backup_with(file.as_str(), false)?;
+                assert!(info.time > 0);
+                assert!(info.size > 0);
+                let res = db.online_backup_with(file.as_str(), false);
+                assert!(matches!(res, Err(EjdbError::IoError(_))));
+                let res = db.online_backup_with(format!("{}-none/db", &file), true);
+                assert!(matches!(res, Err(EjdbError::IoError(_))));
+            }
+            let res = catch(|| {
+                let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+                let count = db.query("@c1/*")?.count()?;
+                assert_eq!(count, 8);
+                let jbl = db.get("c1", 1)?;
+                assert_eq!(jbl.get_str("b")?, "cde1");
+                Ok(())
+            });
+            remove_db_files(&file);
+            res
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_open_locked() {
+        use std::time::{Duration, Instant};
+        let file = tmp_file("locked");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+            let start = Instant::now();
+            let res = crate::EJDB2Builder::new(file.as_str())
+                .open_timeout(Duration::from_millis(50))
+                .build();
+            assert!(matches!(res, Err(EjdbError::Locked(_))));
+            assert!(start.elapsed() >= Duration::from_millis(50));
+            let db = db.reopen(DatabaseOpenMode::empty())?;
+            db.close()?;
+            let db = crate::EJDB2Builder::new(file.as_str())
+                .open_timeout(Duration::from_millis(50))
+                .build()?;
+            db.close()
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_shared_database() {
+        use std::thread;
+        let file = tmp_file("shared");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+            // moved to another thread
+            let db = thread::spawn(move || db.put("c1", "{\"a\":0}", None).map(|_| db))
+                .join()
+                .unwrap()?;
+            let shared = SharedDatabase::new(db);
+            let handles: Vec<_> = (1..=4)
+                .map(|n| {
+                    let shared = shared.clone();
+                    thread::spawn(move || -> Result<()> {
+                        for i in 0..25 {
+                            shared.with(|db| db.put("c1", format!("{{\"a\":{}}}", n), None))?;
+                            let count =
+                                shared.with(|db| db.query(format!("@c1/[a = {}]", n))?.count())?;
+                            assert_eq!(count, i + 1);
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            for h in handles {
+                h.join().unwrap()?;
+            }
+            assert_eq!(shared.lock().query("@c1/*")?.count()?, 101);
+            Ok(())
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_read_only() {
+        let file = tmp_file("rdonly");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+            db.put("c1", "{\"a\":1}", Some(1))?;
+            db.put("c1", "{\"a\":2}", Some(2))?;
+            db.close()?;
+            let db = crate::EJDB2Builder::new(file.as_str()).build_read_only()?;
+            assert_eq!(db.get("c1", 1)?.get_i64("a")?, 1);
+            assert!(db.get_opt("c1", 3)?.is_none());
+            assert_eq!(db.count("c1")?, 2);
+            assert_eq!(db.query("@c1/[a = 2]")?.count()?, 1);
+            assert_eq!(db.collection_names()?, vec!["c1".to_owned()]);
+            db.close()
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_close() {
+        let file = tmp_file("close");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+            db.put("c1", "{\"a\":1}", Some(1))?;
+            db.close()?;
+            let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+            assert!(db.exists("c1", 1)?);
+            db.close()
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_track_mtime() {
+        let file = tmp_file("mtime");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str())
+                .track_mtime("_mtime")
+                .build()?;
+            db.put("c1", "{\"a\":1}", Some(1))?;
+            let t1 = db.get("c1", 1)?.get_i64("_mtime")?;
+            assert!(t1 > 0);
+            db.patch("c1", "[{\"op\":\"add\",\"path\":\"/b\",\"value\":2}]", 1)?;
+            let jbl = db.get("c1", 1)?;
+            assert_eq!(jbl.get_i64("b")?, 2);
+            let t2 = jbl.get_i64("_mtime")?;
+            assert!(t2 >= t1);
+            db.patch("c1", "{\"c\":3}", 1)?;
+            db.merge_or_put("c1", "{\"d\":4}", 2)?;
+            assert!(db.get("c1", 2)?.get_i64("_mtime")? >= t2);
+            let db = db.reopen(DatabaseOpenMode::empty())?;
+            db.put("c1", "{\"a\":1}", Some(3))?;
+            assert!(db.get("c1", 3)?.get_i64("_mtime")? > 0);
+            Ok(())
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_reopen() {
+        let file = tmp_file("reopen");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+            db.put("c1", "{\"a\":1}", Some(1))?;
+            let db = db.reopen(DatabaseOpenMode::IWKV_RDONLY)?;
+            assert_eq!(db.get("c1", 1)?.get_i64("a")?, 1);
+            assert!(db.put("c1", "{\"a\":2}", Some(2)).is_err());
+            let db = db.reopen(DatabaseOpenMode::empty())?;
+            db.put("c1", "{\"a\":2}", Some(2))?;
+            assert!(db.exists("c1", 2)?);
+            Ok(())
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_compact() {
+        let file = tmp_file("compact");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str())
+                .trim_on_close(false)
+                .build()?;
+            for i in 1..=2000 {
+                db.put("c1", format!("{{\"a\":\"{:0>200}\"}}", i), Some(i))?;
+            }
+            db.sync()?;
+            for i in 101..=2000 {
+                db.del("c1", i)?;
+            }
+            db.sync()?;
+            let before = std::fs::metadata(file.as_str())?.len();
+            let db = db.compact()?;
+            let after = std::fs::metadata(file.as_str())?.len();
+            assert!(after < before);
+            assert!(db.exists("c1", 1)?);
+            assert!(!db.exists("c1", 101)?);
+            assert_eq!(db.query("@c1/*")?.count()?, 100);
+            Ok(())
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_compact_truncated() {
+        catch(|| {
+            // temporary database is opened with IWKV_TRUNC
+            let db = crate::EJDB2Builder::temporary().build()?;
+            db.put("c1", "{\"a\":1}", Some(1))?;
+            let db = db.compact()?;
+            assert_eq!(db.get("c1", 1)?.get_i64("a")?, 1);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sync() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            db.put("c1", "{\"a\":1}", None)?;
+            db.sync()?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_path() {
+        let file = tmp_file("path");
+        let res = catch(|| {
+            let db = crate::EJDB2Builder::new(file.as_str()).build()?;
+            assert_eq!(db.path(), file.as_str());
+            db.put("c1", "{\"a\":1}", None)?;
+            db.sync()?;
+            assert!(db.file_size()? > 0);
+            Ok(())
+        });
+        remove_db_files(&file);
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_put_within_query() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let res = db.query("@c1/*")?.for_each(|_doc| {
+                db.put("c1", "{\"a\":1}", None)?;
+                Ok(())
+            });
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            db.put("c1", "{\"a\":1}", None)?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_txn() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let res = db.read_txn(|db| db.del("c1", 1));
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            let res = db.read_txn(|db| db.write_txn(|_| Ok(())));
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            let res = db.write_txn(|db| db.write_txn(|_| Ok(())));
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            let count = db.write_txn(|db| {
+                db.del("c1", 1)?;
+                db.query("@c1/*")?.count()
+            })?;
+            assert_eq!(count, 7);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_put_within_query_in_write_txn() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let res = db.write_txn(|db| {
+                db.query("@c1/*")?.for_each(|_doc| {
+                    db.put("c1", "{\"a\":1}", None)?;
+                    Ok(())
+                })
+            });
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            let res = db.write_txn(|db| db.read_txn(|db| db.del("c1", 1)));
+            assert!(matches!(res, Err(EjdbError::TxnConflict(_))));
+            // writes after the query finished are allowed
+            db.write_txn(|db| {
+                db.query("@c1/*")?.count()?;
+                db.put("c1", "{\"a\":1}", None)
+            })?;
+            Ok(())
+        })
+        .unwrap();
+    }
 }
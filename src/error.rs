@@ -1,4 +1,6 @@
-use crate::{ffi::iwlog_ecode_explained as decode, xstr::XString};
+use crate::{ffi::iwlog_ecode_explained as decode, jbl::JsonType, utils, xstr::XString};
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::boxed::Box;
 use core::{any::Any, fmt, str::Utf8Error};
 #[cfg(feature = "std")]
 use std::{error::Error as StdError, ffi::NulError, io};
@@ -11,6 +13,10 @@ pub enum EjdbError {
         rc: u64,
         file: XString,
     },
+    /// database file not found while not allowed to create
+    DatabaseNotFound(XString),
+    /// database file is locked by another open database
+    Locked(XString),
     /// allocation failure
     AllocError,
     /// invalid json data
@@ -25,10 +31,74 @@ pub enum EjdbError {
         error: XString,
     },
 
+    /// JSON value is not of expected type
+    UnexpectedType {
+        expected: JsonType,
+        actual: JsonType,
+    },
+
+    /// number does not fit in requested type
+    OutOfRange(i64),
+
+    /// value can not be represented in JSON, e.g. NaN or infinity
+    InvalidValue(&'static str),
+
+    /// payload size in bytes exceeds limit
+    TooLarge {
+        size: usize,
+        max: usize,
+    },
+
+    /// query matched more docs than allowed
+    ResultLimitExceeded(usize),
+
+    /// malformed regular expression
+    InvalidRegex(&'static str),
+
+    /// collection does not exist
+    CollectionNotFound(XString),
+
+    /// collection already exists
+    CollectionExists(XString),
+
+    /// name matches several collections case-insensitively
+    AmbiguousCollection(XString),
+
+    /// document was changed since its token was read, or removed
+    Conflict(i64),
+
+    /// query placeholder is not bound before exec
+    UnboundPlaceholder(XString),
+
+    /// read/write scope misuse, e.g. write within a read scope
+    TxnConflict(&'static str),
+
     /// IO related error
     #[cfg(feature = "std")]
     IoError(io::Error),
 
+    /// batch operation failed at document `id`, after `done` succeeded
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    BatchError {
+        id: i64,
+        done: usize,
+        error: Box<EjdbError>,
+    },
+
+    /// failure at given line of imported data, counted from 1
+    #[cfg(feature = "std")]
+    ImportError {
+        line: usize,
+        error: Box<EjdbError>,
+    },
+
+    /// HTTP endpoint can not be bound
+    #[cfg(feature = "std")]
+    HttpBindError {
+        port: u16,
+        error: io::Error,
+    },
+
     /// Panic from catch_unwind
     #[cfg(feature = "std")]
     Panic(Box<dyn Any + Send>),
@@ -38,6 +108,59 @@ pub enum EjdbError {
     Other(Box<dyn StdError + 'static>),
 }
 
+impl EjdbError {
+    /// true if error means document, collection or JSON path not found
+    #[inline]
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Self::Generic(rc) => utils::is_not_found(*rc),
+            Self::CollectionNotFound(_) => true,
+            _ => false,
+        }
+    }
+
+    /// render message as `Display` does into `buf` without allocation,
+    /// including decoded text of EJDB2 error code; truncated to fit at
+    /// char boundary, no trailing NUL, returns number of bytes written
+    pub fn write_into(&self, buf: &mut [u8]) -> usize {
+        let mut w = SliceWriter { buf, len: 0 };
+        let _ = fmt::Write::write_fmt(&mut w, format_args!("{}", self));
+        w.len
+    }
+}
+
+/// fixed buffer of error message
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for SliceWriter<'_> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = self.buf.len() - self.len;
+        let mut n = core::cmp::min(s.len(), room);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        // stop formatting once full
+        if n < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// explanation text of EJDB2 error code, e.g. of `EjdbError::Generic`;
+/// empty for unknown codes
+#[inline]
+pub fn explain_code(rc: u64) -> &'static str {
+    decode(rc)
+}
+
 impl fmt::Debug for EjdbError {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -57,15 +180,61 @@ impl fmt::Display for EjdbError {
                     decode(*rc)
                 )
             }
+            Self::DatabaseNotFound(file) => write!(f, "EJDB2 database not found: {}", file),
+            Self::Locked(file) => write!(f, "EJDB2 database file is locked: {}", file),
             Self::Generic(rc) => write!(f, "EJDB2 error: {}", decode(*rc)),
             Self::JQLParseError { rc, error } => {
                 write!(f, "{}: {}", decode(*rc), error)
             }
+            Self::UnexpectedType { expected, actual } => {
+                write!(
+                    f,
+                    "Unexpected JSON type: expected {:?}, got {:?}",
+                    expected, actual
+                )
+            }
+            Self::OutOfRange(val) => write!(f, "Value out of range: {}", val),
+            Self::InvalidValue(msg) => write!(f, "Invalid value: {}", msg),
+            Self::TooLarge { size, max } => {
+                write!(
+                    f,
+                    "Payload of {} bytes exceeds limit of {} bytes",
+                    size, max
+                )
+            }
+            Self::ResultLimitExceeded(max) => {
+                write!(f, "Query result exceeds limit of {} docs", max)
+            }
+            Self::InvalidRegex(msg) => write!(f, "Invalid regular expression: {}", msg),
+            Self::CollectionNotFound(name) => write!(f, "Collection not found: {}", name),
+            Self::CollectionExists(name) => write!(f, "Collection already exists: {}", name),
+            Self::AmbiguousCollection(name) => {
+                write!(f, "Collection name matches several collections: {}", name)
+            }
+            Self::Conflict(id) => write!(f, "Document {} was changed concurrently", id),
+            Self::UnboundPlaceholder(name) => write!(f, "Placeholder is not bound: {}", name),
+            Self::TxnConflict(msg) => write!(f, "Transaction conflict: {}", msg),
             Self::AllocError => write!(f, "Failed to allocate memory"),
             Self::InvalidJson(rc) => write!(f, "Invalid json data: {}", decode(*rc)),
             Self::Utf8Error(e) => write!(f, "IO error: {}", e),
             #[cfg(feature = "std")]
             Self::IoError(e) => write!(f, "IO error: {}", e),
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            Self::BatchError { id, done, error } => {
+                write!(
+                    f,
+                    "Batch failed at id {} after {} done: {}",
+                    id, done, error
+                )
+            }
+            #[cfg(feature = "std")]
+            Self::ImportError { line, error } => {
+                write!(f, "Import failed at line {}: {}", line, error)
+            }
+            #[cfg(feature = "std")]
+            Self::HttpBindError { port, error } => {
+                write!(f, "Failed to bind HTTP port {}: {}", port, error)
+            }
             #[cfg(feature = "std")]
             Self::Panic(_e) => write!(f, "Unwind panic captured"),
             #[cfg(feature = "std")]
@@ -94,11 +263,26 @@ impl From<io::Error> for EjdbError {
         Self::IoError(e)
     }
 }
+/// `IoError` is unwrapped, other errors map to `ErrorKind::Other`
+/// with the error as source
+#[cfg(feature = "std")]
+impl From<EjdbError> for io::Error {
+    #[inline]
+    fn from(e: EjdbError) -> Self {
+        match e {
+            EjdbError::IoError(e) => e,
+            e => io::Error::new(io::ErrorKind::Other, e),
+        }
+    }
+}
 #[cfg(feature = "std")]
 impl StdError for EjdbError {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
             Self::IoError(e) => Some(e),
+            Self::HttpBindError { error, .. } => Some(error),
+            Self::ImportError { error, .. } => Some(error.as_ref()),
+            Self::BatchError { error, .. } => Some(error.as_ref()),
             Self::Other(e) => Some(e.as_ref()),
             _ => None,
         }
@@ -107,3 +291,37 @@ impl StdError for EjdbError {
 
 unsafe impl Send for EjdbError {}
 unsafe impl Sync for EjdbError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_explain_code() {
+        let rc = ejdb2_sys::ejdb_ecode_t::EJDB_ERROR_COLLECTION_NOT_FOUND as u64;
+        let msg = explain_code(rc);
+        assert!(msg.contains("EJDB_ERROR_COLLECTION_NOT_FOUND"));
+        assert_eq!(
+            EjdbError::Generic(rc).to_string(),
+            format!("EJDB2 error: {}", msg)
+        );
+    }
+
+    #[test]
+    fn test_write_into() {
+        let e = EjdbError::Generic(70004);
+        let mut buf = [0_u8; 256];
+        let n = e.write_into(&mut buf);
+        assert_eq!(&buf[..n], e.to_string().as_bytes());
+
+        let mut buf = [0_u8; 8];
+        assert_eq!(e.write_into(&mut buf), 8);
+        assert_eq!(&buf, &e.to_string().as_bytes()[..8]);
+
+        // "Invalid regular expression: " is 28 bytes, 'é' is cut
+        let e = EjdbError::InvalidRegex("é");
+        let mut buf = [0_u8; 29];
+        assert_eq!(e.write_into(&mut buf), 28);
+        assert_eq!(e.write_into(&mut []), 0);
+    }
+}
@@ -1,17 +1,20 @@
 use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
-use core::{cell::UnsafeCell, ffi::c_void, mem};
+use core::{cell::UnsafeCell, ffi::c_void, fmt, mem, ptr};
 
 use crate::{
     channel::Channel,
+    jbl::{JBLType, JsonType, JBL},
     jql::{self, JQL},
     printer,
     printer::{AsJson, JsonPrinter},
     utils::{self, check_rc},
-    xstr::XString,
+    xstr::{StringPtr, XString},
     Database, EjdbError, JsonPrintFlags, Result,
 };
 
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
 #[cfg(feature = "std")]
 use std::{collections::HashMap, hash::Hash};
 
@@ -63,12 +66,99 @@ impl<'a> Query<'a> {
         self.log = Some(UnsafeCell::new(f));
         self
     }
+
+    /// same query against another collection, see `JQL::with_collection`;
+    /// skip, limit and log are kept, placeholder values are not
+    #[inline]
+    pub fn for_collection<'b>(&self, collection: impl Into<StringPtr<'b>>) -> Result<Self> {
+        let jql = self.jql.with_collection(collection)?;
+        Ok(Self {
+            db: self.db,
+            jql,
+            skip: self.skip,
+            limit: self.limit,
+            log: self
+                .log
+                .as_ref()
+                .map(|c| UnsafeCell::new(unsafe { *c.get() })),
+        })
+    }
+    /// order results by `path`, see `JQL::with_order`;
+    /// call it before binding placeholders, as their values are not kept
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn order_by(&mut self, path: &str, ascending: bool) -> Result<()> {
+        self.jql = self.jql.with_order(path, ascending)?;
+        Ok(())
+    }
+    /// check all placeholders are bound, see [`JQL::ensure_bound`]
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn ensure_bound(&self) -> Result<()> {
+        self.jql.ensure_bound()
+    }
+    /// reset query to run it again, see `JQL::reset`;
+    /// `reset_cache` clears the match cache kept from previous execution,
+    /// `reset_placeholders` clears bound values, so bind them again
+    #[inline]
+    pub fn reset(&mut self, reset_cache: bool, reset_placeholders: bool) -> &mut Self {
+        self.jql.reset(reset_cache, reset_placeholders);
+        self
+    }
+
     /// exec query and return matched count
     #[inline]
     pub fn count(&self) -> Result<usize> {
+        // aggregate count query visits no doc
+        if self.jql.is_aggregate_count() {
+            return self.count_with_skip();
+        }
         self.fold(0_usize, |acc, _| Ok(acc + 1))
     }
 
+    /// exec query and return matched count, respecting both skip and limit;
+    /// docs are counted by EJDB2 without visiting, `| count` queries included
+    /// Note: no query plan log for this query
+    #[inline]
+    pub fn count_with_skip(&self) -> Result<usize> {
+        let cnt = self.scalar_i64()?;
+        Ok(if cnt < 0 { 0 } else { cnt as usize })
+    }
+
+    /// exec query and return the scalar counter of EJDB2 exec,
+    /// which is the result of `| count` queries, or number of
+    /// matched docs otherwise; no doc is visited
+    /// Note: no query plan log for this query
+    #[inline]
+    pub fn scalar_i64(&self) -> Result<i64> {
+        let _guard = self.db.enter_read();
+        self.exec_scalar()
+    }
+
+    /// exec query within write scope and return number of matched docs,
+    /// for queries with `apply` or `del`; joins the current write scope if any
+    #[inline]
+    pub(crate) fn count_written(&self) -> Result<usize> {
+        let cnt = self.db.join_write_txn(|_| self.exec_scalar())?;
+        Ok(if cnt < 0 { 0 } else { cnt as usize })
+    }
+
+    #[inline]
+    fn exec_scalar(&self) -> Result<i64> {
+        let mut ux = sys::_EJDB_EXEC::default();
+        ux.db = self.db.raw_ptr();
+        ux.q = self.jql.raw_ptr();
+        if let Some(skip) = self.skip {
+            ux.skip = skip as i64;
+        }
+        if let Some(limit) = self.limit {
+            ux.limit = limit as i64;
+        }
+        let rc = unsafe { sys::ejdb_exec(&mut ux as *mut _) };
+        check_rc(rc)?;
+        Ok(ux.cnt)
+    }
+
     /// exec query and return matched count
     /// Note: no query plan log for this query
     #[inline]
@@ -82,6 +172,20 @@ impl<'a> Query<'a> {
         check_rc(rc).map(|_| if count < 0 { 0 } else { count as usize })
     }
 
+    /// exec query and return ids of matched docs where rfc6901 `path` is
+    /// absent, unlike null; checked on each doc by this crate, as EJDB2
+    /// conditions never match absent properties
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn missing_ids<'b>(&self, path: impl Into<StringPtr<'b>>) -> Result<Vec<i64>> {
+        let path = path.into().to_owned();
+        self.fold(Vec::new(), |mut ids, doc| {
+            if doc.to_jbl()?.type_at(&path)? == JsonType::None {
+                ids.push(doc.id());
+            }
+            Ok(ids)
+        })
+    }
+
     /// exec query and return true if any matched doc
     #[inline]
     pub fn any(&self) -> Result<bool> {
@@ -102,6 +206,22 @@ impl<'a> Query<'a> {
         self.exec_with(&mut visitor)?;
         visitor.v
     }
+    /// exec query and extract a single value from the first row,
+    /// other rows are not visited; `None` if no row, note `| count`
+    /// queries return no row, use `scalar_i64` for them
+    #[inline]
+    pub fn value<F, T>(&self, f: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&JsonDoc) -> Result<T>,
+    {
+        let mut f = Some(f);
+        // visitor stops after the first row, fail rather than panic
+        // across FFI if it is called again anyway
+        self.first(|doc| match f.take() {
+            Some(f) => f(doc),
+            None => Err(EjdbError::ResultLimitExceeded(1)),
+        })
+    }
     #[inline]
     pub fn first_or_default<F, T>(&self, f: F) -> Result<T>
     where
@@ -110,7 +230,9 @@ impl<'a> Query<'a> {
     {
         self.first(f).map(|x| x.unwrap_or_default())
     }
-    /// exec query and return all matched docs
+    /// exec query and return all matched docs;
+    /// beware all docs are kept in memory, use `to_vec_capped`
+    /// for unbounded queries, e.g. from user input
     #[cfg(any(feature = "std", feature = "alloc"))]
     #[inline]
     pub fn to_vec<F, T>(&self, mut f: F) -> Result<Vec<T>>
@@ -126,7 +248,7 @@ impl<'a> Query<'a> {
     }
 
     /// exec query and return all matched docs
-    #[cfg(any(feature = "std"))]
+    #[cfg(feature = "std")]
     #[inline]
     pub fn to_map<F, K, V>(&self, mut f: F) -> Result<HashMap<K, V>>
     where
@@ -145,6 +267,86 @@ impl<'a> Query<'a> {
         })
     }
 
+    /// exec query and return all matched docs
+    #[cfg(all(feature = "alloc", not(feature = "std")))]
+    #[inline]
+    pub fn to_map<F, K, V>(&self, f: F) -> Result<BTreeMap<K, V>>
+    where
+        F: FnMut(&JsonDoc) -> Result<(K, V)>,
+        K: Ord,
+    {
+        self.to_btree_map(f)
+    }
+
+    /// exec query and return all matched docs, or
+    /// `EjdbError::ResultLimitExceeded` if more than `max` docs matched;
+    /// query is stopped once the cap is reached
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn to_vec_capped<F, T>(&self, max: usize, mut f: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&JsonDoc) -> Result<T>,
+    {
+        let mut exceeded = false;
+        let items = self.scan(Vec::new(), |acc, doc| {
+            if acc.len() == max {
+                exceeded = true;
+                return Ok(None);
+            }
+            let v = (f)(doc)?;
+            let mut items = mem::take(acc);
+            items.push(v);
+            Ok(Some(items))
+        })?;
+        if exceeded {
+            return Err(EjdbError::ResultLimitExceeded(max));
+        }
+        Ok(items)
+    }
+
+    /// exec query and store docs transformed by `f` under the same id,
+    /// `None` leaves doc untouched; returns number of updated docs.
+    ///
+    /// Note: collection is locked while iterating, so updated docs are kept
+    /// in memory and written after query finishes, within the current
+    /// write scope or a new one
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn update_each<F>(&self, mut f: F) -> Result<usize>
+    where
+        F: FnMut(&JsonDoc) -> Result<Option<JBL>>,
+    {
+        let updates = self.fold(Vec::new(), |acc, doc| {
+            let mut acc = acc;
+            if let Some(jbl) = (f)(doc)? {
+                acc.push((doc.id(), jbl));
+            }
+            Ok(acc)
+        })?;
+        let coll = self.jql.collection()?;
+        self.db.join_write_txn(|db| {
+            for (id, jbl) in updates.iter() {
+                db.put_jbl(&coll, jbl, Some(*id))?;
+            }
+            Ok(updates.len())
+        })
+    }
+
+    /// exec query and return all matched docs ordered by key
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn to_btree_map<F, K, V>(&self, mut f: F) -> Result<BTreeMap<K, V>>
+    where
+        F: FnMut(&JsonDoc) -> Result<(K, V)>,
+        K: Ord,
+    {
+        self.fold(BTreeMap::new(), |acc, doc| {
+            let mut acc = acc;
+            let (k, v) = (f)(doc)?;
+            acc.insert(k, v);
+            Ok(acc)
+        })
+    }
+
     /// exec query and aggregate value based on all matched docs
     #[inline]
     pub fn fold<F, T>(&self, initial: T, mut f: F) -> Result<T>
@@ -159,6 +361,24 @@ impl<'a> Query<'a> {
         .map(|_| acc.unwrap())
     }
 
+    /// exec query and stream matched docs into writer as JSON array,
+    /// return number of docs written; docs are not buffered, so output
+    /// written before an error is left in the writer
+    #[cfg(feature = "std")]
+    pub fn write_json_array<W: std::io::Write>(&self, w: &mut W) -> Result<usize> {
+        w.write_all(b"[")?;
+        let count = self.fold(0_usize, |n, doc| {
+            if n > 0 {
+                w.write_all(b",")?;
+            }
+            doc.print(&mut *w, None)?;
+            Ok(n + 1)
+        })?;
+        w.write_all(b"]")?;
+        w.flush()?;
+        Ok(count)
+    }
+
     #[inline]
     pub fn for_each<F>(&self, f: F) -> Result<()>
     where
@@ -189,25 +409,124 @@ impl<'a> Query<'a> {
     }
 
     pub fn exec(&self) -> Result<()> {
-        self.exec_with(&mut visitor_impl::Empty {})
+        self.exec_with(&mut visitor_impl::Empty {}).and(Ok(()))
+    }
+
+    /// exec query with visitor and return number of processed docs
+    pub fn exec_with<V: Visitor>(&self, visitor: &mut V) -> Result<usize> {
+        self.exec_range(visitor, self.skip, self.limit, None)
+    }
+
+    /// exec query and return query plan.
+    ///
+    /// Note: query is executed up to the first matched doc,
+    /// which is modified if query has `apply` or `del`
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn explain(&self) -> Result<String> {
+        let xstr = XString::new();
+        self.exec_range(
+            &mut visitor_impl::Empty {},
+            self.skip,
+            self.limit,
+            Some(&xstr),
+        )?;
+        Ok(String::from(xstr.as_str()))
+    }
+
+    /// exec query and return query plan with fields parsed from it,
+    /// see `ExplainInfo`.
+    ///
+    /// Note: query is executed over all matched docs, which are
+    /// modified if query has `apply` or `del`
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn explain_info(&self) -> Result<ExplainInfo> {
+        let xstr = XString::new();
+        let mut visitor = visitor_impl::ForEachVisitor {
+            q: self,
+            f: |_: &JsonDoc| Ok(()),
+            v: Ok(()),
+        };
+        let matched = self.exec_range(&mut visitor, self.skip, self.limit, Some(&xstr))?;
+        visitor.get()?;
+        Ok(ExplainInfo::parse(String::from(xstr.as_str()), matched))
+    }
+
+    /// exec query page by page; each page fetches at most `page_size` docs
+    /// with skip/limit, starting from `skip()` of this query.
+    ///
+    /// Note: pages are fetched on demand, if docs are inserted or removed
+    /// between pages, some docs might be skipped or returned twice.
+    ///
+    /// fails with `EjdbError::InvalidValue` if `page_size` is 0
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn paginate(&self, page_size: usize) -> Result<Page<'_, 'a>> {
+        if page_size == 0 {
+            return Err(EjdbError::InvalidValue("page_size must be greater than 0"));
+        }
+        self.fetch_page(0, page_size)
     }
 
-    pub fn exec_with<V: Visitor>(&self, visitor: &mut V) -> Result<()> {
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    fn fetch_page(&self, offset: usize, page_size: usize) -> Result<Page<'_, 'a>> {
+        // fetch one more doc to tell if there is more
+        let mut fetch = page_size + 1;
+        if let Some(limit) = self.limit {
+            fetch = core::cmp::min(fetch, limit.saturating_sub(offset));
+        }
+        let mut items = Vec::new();
+        if fetch > 0 {
+            let mut visitor = visitor_impl::ForEachVisitor {
+                q: self,
+                f: |doc: &JsonDoc| {
+                    items.push(doc.to_jbl()?);
+                    Ok(())
+                },
+                v: Ok(()),
+            };
+            let skip = self.skip.unwrap_or(0) + offset;
+            self.exec_range(&mut visitor, Some(skip), Some(fetch), None)?;
+            visitor.get()?;
+        }
+        let has_more = items.len() > page_size;
+        items.truncate(page_size);
+        Ok(Page {
+            query: self,
+            offset,
+            page_size,
+            items,
+            has_more,
+        })
+    }
+
+    fn exec_range<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        skip: Option<usize>,
+        limit: Option<usize>,
+        log: Option<&XString>,
+    ) -> Result<usize> {
+        // writes from within visitor would deadlock on collection lock
+        let _guard = self.db.enter_read();
         let mut chan = Channel(visitor, Ok(VisitStep::Stop));
         let mut ux = sys::_EJDB_EXEC::default();
         ux.db = self.db.raw_ptr();
         ux.q = self.jql.raw_ptr();
         ux.visitor = Some(visit_doc::<V>);
-        if let Some(skip) = self.skip {
+        if let Some(skip) = skip {
             ux.skip = skip as i64;
         }
-        if let Some(limit) = self.limit {
+        if let Some(limit) = limit {
             ux.limit = limit as i64;
         }
         ux.opaque = &mut chan as *mut _ as *mut c_void;
 
-        let rc = match self.log {
-            Some(ref c) => {
+        let rc = match (log, &self.log) {
+            (Some(xstr), _) => {
+                ux.log = xstr.as_mut_ptr();
+                unsafe { sys::ejdb_exec(&mut ux as *mut _) }
+            }
+            (None, Some(c)) => {
                 let xstr = XString::new();
                 ux.log = xstr.as_mut_ptr();
                 let rc = unsafe { sys::ejdb_exec(&mut ux as *mut _) };
@@ -218,7 +537,81 @@ impl<'a> Query<'a> {
             _ => unsafe { sys::ejdb_exec(&mut ux as *mut _) },
         };
         chan.get()?;
-        check_rc(rc)
+        check_rc(rc)?;
+        Ok(if ux.cnt < 0 { 0 } else { ux.cnt as usize })
+    }
+}
+
+/// structured view of query plan, see `Query::explain_info`;
+/// EJDB2 does not report the number of scanned docs, so only
+/// the matched count is available
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainInfo {
+    /// raw query plan text
+    pub plan: String,
+    /// whether an index is selected for the query
+    pub index_used: bool,
+    /// path of the selected index, e.g. `/c`
+    pub index_path: Option<String>,
+    /// number of matched docs, respecting skip and limit
+    pub matched: usize,
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl ExplainInfo {
+    /// parse known fields from plan text,
+    /// e.g. `[INDEX] SELECTED UNIQUE|I64 /c EXPR1: 'c > 4' INIT: IWKV_CURSOR_GE`
+    fn parse(plan: String, matched: usize) -> Self {
+        let mut index_path = None;
+        let index_used = match plan.find("[INDEX] SELECTED") {
+            Some(pos) => {
+                index_path = plan[pos..]
+                    .split_whitespace()
+                    .find(|s| s.starts_with('/'))
+                    .map(String::from);
+                true
+            }
+            None => false,
+        };
+        Self {
+            plan,
+            index_used,
+            index_path,
+            matched,
+        }
+    }
+}
+
+/// page of query result, see `Query::paginate`
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub struct Page<'q, 'a> {
+    query: &'q Query<'a>,
+    offset: usize,
+    page_size: usize,
+    /// docs of this page
+    pub items: Vec<JBL>,
+    /// true if there are more docs after this page
+    pub has_more: bool,
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl<'q, 'a> Page<'q, 'a> {
+    /// offset of first doc, relative to `skip()` of the query
+    #[inline(always)]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// fetch next page, returns None if no more docs
+    #[inline]
+    pub fn next(&self) -> Result<Option<Page<'q, 'a>>> {
+        if !self.has_more {
+            return Ok(None);
+        }
+        self.query
+            .fetch_page(self.offset + self.page_size, self.page_size)
+            .map(Some)
     }
 }
 
@@ -383,6 +776,26 @@ impl JsonDoc {
         unsafe { &mut *self.doc }
     }
 
+    /// size of stored binary document in bytes;
+    /// projection or `apply` of query is not reflected
+    #[inline]
+    pub fn size(&self) -> usize {
+        unsafe { sys::jbl_size(self.doc().raw) as usize }
+    }
+
+    /// buffer of stored binary document, only valid while visiting;
+    /// projection or `apply` of query is not reflected
+    #[inline]
+    pub fn raw_bytes(&self) -> &[u8] {
+        let mut buf = ptr::null_mut();
+        let mut size = 0;
+        let rc = unsafe { sys::jbl_as_buf(self.doc().raw, &mut buf, &mut size) };
+        if rc != 0 || buf.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(buf as *const u8, size as usize) }
+    }
+
     #[inline]
     pub fn print<T: JsonPrinter>(
         &self,
@@ -392,14 +805,43 @@ impl JsonDoc {
         let flag = flag.unwrap_or(JsonPrintFlags::PRINT_CODEPOINTS);
         printer::doc_print_json(self.doc, target, flag)
     }
-}
 
-impl AsJson<XString> for JsonDoc {
-    /// more efficient than use print() for XString
-    fn as_json(&self, flag: Option<JsonPrintFlags>) -> Result<XString> {
-        let size = unsafe { sys::jbl_size(self.doc().raw) as usize };
-        let xstr = XString::new_with_size(size * 2);
-        let xstr_ptr = xstr.as_mut_ptr() as *mut c_void;
+    /// copy document into an owned JBL, which is safe to keep after visiting
+    #[inline]
+    pub fn to_jbl(&self) -> Result<JBL> {
+        let doc = self.doc();
+        if !doc.node.is_null() {
+            let is_array = unsafe { (*doc.node).type_ == JBLType::JBV_ARRAY };
+            let jbl = if is_array {
+                JBL::new_array()?
+            } else {
+                JBL::new_object()?
+            };
+            let rc = unsafe { sys::jbl_fill_from_node(jbl.raw_ptr(), doc.node) };
+            check_rc(rc).and(Ok(jbl))
+        } else {
+            let mut h = ptr::null_mut();
+            let rc = unsafe { sys::jbl_clone(doc.raw, &mut h) };
+            check_rc(rc)?;
+            Ok(JBL::from_ptr(h))
+        }
+    }
+
+    /// to indented JSON string, non-ASCII chars are escaped
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn to_pretty_string(&self) -> Result<String> {
+        self.as_json(Some(
+            JsonPrintFlags::PRINT_PRETTY | JsonPrintFlags::PRINT_CODEPOINTS,
+        ))
+    }
+
+    /// print json into buffer, which is cleared first;
+    /// reuse buffer to avoid allocation for each call of `as_json()`
+    #[inline]
+    pub fn write_json_into(&self, buf: &mut XString, flag: Option<JsonPrintFlags>) -> Result<()> {
+        buf.clear();
+        let xstr_ptr = buf.as_mut_ptr() as *mut c_void;
         let flag = flag.unwrap_or(JsonPrintFlags::PRINT_CODEPOINTS).bits;
         let rc = unsafe {
             if !self.doc().node.is_null() {
@@ -418,8 +860,36 @@ impl AsJson<XString> for JsonDoc {
                 )
             }
         };
-        check_rc(rc)?;
-        Ok(xstr)
+        check_rc(rc)
+    }
+}
+
+impl AsJson<XString> for JsonDoc {
+    /// more efficient than use print() for XString
+    fn as_json(&self, flag: Option<JsonPrintFlags>) -> Result<XString> {
+        let size = unsafe { sys::jbl_size(self.doc().raw) as usize };
+        let mut xstr = XString::new_with_size(size * 2);
+        self.write_json_into(&mut xstr, flag).and(Ok(xstr))
+    }
+}
+
+impl fmt::Display for JsonDoc {
+    /// compact JSON, or pretty JSON with `{:#}`
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = printer::fmt_flags(f);
+        let res: Result<XString> = self.as_json(Some(flag));
+        match res {
+            Ok(s) => write!(f, "{}", s),
+            Err(_) => write!(f, "{}", printer::INVALID_JSON),
+        }
+    }
+}
+
+impl fmt::Debug for JsonDoc {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JsonDoc{{id={}, json={}}}", self.id(), self)
     }
 }
 
@@ -458,6 +928,43 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_for_collection() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            db.put("c2", "{\"c\":9}", None)?;
+            let q1 = db.query("@c1/[c = 9]")?;
+            let q2 = q1.for_collection("c2")?;
+            assert_eq!(q1.count()?, 1);
+            assert_eq!(q2.count()?, 1);
+            let jbl = q2.first(|doc| doc.to_jbl())?.unwrap();
+            assert!(jbl.get_str("a").is_err());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_count_with_skip() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let count = db.query("@c1/*")?.count_with_skip()?;
+            assert_eq!(count, 8);
+            let count = db.query("@c1/*")?.skip(2).take(3).count_with_skip()?;
+            assert_eq!(count, 3);
+            let count = db.query("@c1/*")?.skip(6).take(3).count_with_skip()?;
+            assert_eq!(count, 2);
+            let count = db.query("@c1/*")?.skip(10).count_with_skip()?;
+            assert_eq!(count, 0);
+            let count = db.query("@c1/[c > 2]")?.skip(1).count_with_skip()?;
+            assert_eq!(count, 3);
+            assert_eq!(db.query("@c1/* | count")?.count()?, 8);
+            assert_eq!(db.query("@c1/* | count")?.count_with_skip()?, 8);
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_log() {
         catch(|| {
@@ -473,6 +980,71 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_exec_with_count() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            struct Counter(usize);
+            impl Visitor for Counter {
+                fn on_next(&mut self, _doc: &JsonDoc) -> Result<VisitStep> {
+                    self.0 += 1;
+                    Ok(VisitStep::Next)
+                }
+            }
+            let mut visitor = Counter(0);
+            let count = db.query("@c1/[c > 4]")?.exec_with(&mut visitor)?;
+            assert_eq!(count, 2);
+            assert_eq!(visitor.0, 2);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_explain() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let plan = db.query("@c1/[c > 4]")?.explain()?;
+            assert!(!plan.is_empty());
+            assert!(plan.contains("[INDEX]"), "{}", plan);
+            assert!(plan.contains("[COLLECTOR]"), "{}", plan);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_explain_info() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let info = db.query("@c1/[c > 4]")?.explain_info()?;
+            assert!(!info.index_used);
+            assert_eq!(info.index_path, None);
+            assert_eq!(info.matched, 2);
+            assert!(!info.plan.is_empty());
+
+            db.collection("c1").ensure_i64_index("/c")?;
+            let info = db.query("@c1/[c > 4]")?.explain_info()?;
+            assert!(info.index_used, "{}", info.plan);
+            assert_eq!(info.index_path.as_deref(), Some("/c"));
+            assert_eq!(info.matched, 2);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_explain_info_parse() {
+        let plan = "[INDEX] SELECTED UNIQUE|STR /name EXPR1: 'name = :?' INIT: IWKV_CURSOR_EQ\n \
+                    [COLLECTOR] PLAIN\n";
+        let info = ExplainInfo::parse(String::from(plan), 1);
+        assert!(info.index_used);
+        assert_eq!(info.index_path.as_deref(), Some("/name"));
+        let info = ExplainInfo::parse(String::from("[INDEX] NO [COLLECTOR] PLAIN\n"), 0);
+        assert!(!info.index_used);
+        assert_eq!(info.index_path, None);
+    }
+
     #[test]
     fn test_any_is_true() {
         catch(|| {
@@ -539,6 +1111,23 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_value() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            assert_eq!(db.query("@c1/* | count")?.scalar_i64()?, 8);
+            assert_eq!(db.query("@c1/[c > 2] | count")?.scalar_i64()?, 4);
+            let c = db
+                .query("@c1/[c > 2] | asc /c")?
+                .value(|doc| doc.to_jbl()?.get_i64("c"))?;
+            assert_eq!(c, Some(3));
+            let none = db.query("@c1/[c > 100]")?.value(|doc| Ok(doc.id()))?;
+            assert_eq!(none, None);
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_first() {
         catch(|| {
@@ -555,6 +1144,158 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_to_jbl() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let jbl = db.query("@c1/[c = 9]")?.first(|doc| doc.to_jbl())?.unwrap();
+            assert_eq!(jbl.get_str("a")?, "abc8");
+            assert_eq!(jbl.get_i64("c")?, 9);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_doc_size() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let total = db.query("@c1/*")?.fold(0, |acc, doc| {
+                assert_eq!(doc.size(), doc.raw_bytes().len());
+                assert!(doc.size() > 0);
+                Ok(acc + doc.size())
+            })?;
+            assert!(total > 0);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_doc_display() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let json = db.query("@c1/[c = 9]")?.first(|doc| Ok(doc.to_string()))?;
+            assert_eq!(json.unwrap(), "{\"a\":\"abc8\",\"b\":\"cde6\",\"c\":9}");
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_to_vec_capped() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let items = db.query("@c1/*")?.to_vec_capped(8, |doc| Ok(doc.id()))?;
+            assert_eq!(items.len(), 8);
+            let mut count = 0;
+            let res = db.query("@c1/*")?.to_vec_capped(3, |doc| {
+                count += 1;
+                Ok(doc.id())
+            });
+            assert!(matches!(res, Err(EjdbError::ResultLimitExceeded(3))));
+            assert_eq!(count, 3);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_update_each() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let count = db.query("@c1/[c > 0]")?.update_each(|doc| {
+                let mut jbl = doc.to_jbl()?;
+                let c = jbl.get_i64("c")?;
+                if c == 9 {
+                    return Ok(None);
+                }
+                jbl.merge(format!("{{\"c\":{}}}", c + 1))?;
+                Ok(Some(jbl))
+            })?;
+            assert_eq!(count, 5);
+            assert_eq!(db.get("c1", 3)?.get_i64("c")?, 6);
+            assert_eq!(db.get("c1", 7)?.get_i64("c")?, 2);
+            assert_eq!(db.get("c1", 8)?.get_i64("c")?, 9);
+            assert_eq!(db.get("c1", 1)?.get_i64("c")?, 0);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_update_each_in_write_txn() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let count = db.write_txn(|db| {
+                db.query("@c1/[c = 5]")?.update_each(|doc| {
+                    let mut jbl = doc.to_jbl()?;
+                    jbl.merge("{\"c\":50}")?;
+                    Ok(Some(jbl))
+                })
+            })?;
+            assert_eq!(count, 1);
+            assert_eq!(db.get("c1", 3)?.get_i64("c")?, 50);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_to_btree_map() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let map = db
+                .query("@c1/*")?
+                .to_btree_map(|doc| Ok((doc.id(), doc.to_jbl()?.get_str("a")?)))?;
+            assert_eq!(map.len(), 8);
+            assert_eq!(map[&8], "abc8");
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_paginate() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let query = db.query("@c1/*")?;
+            let page = query.paginate(3)?;
+            assert_eq!(page.items.len(), 3);
+            assert!(page.has_more);
+            let page = page.next()?.unwrap();
+            assert_eq!(page.offset(), 3);
+            assert_eq!(page.items.len(), 3);
+            assert!(page.has_more);
+            let page = page.next()?.unwrap();
+            assert_eq!(page.items.len(), 2);
+            assert!(!page.has_more);
+            assert!(page.next()?.is_none());
+            let res = query.paginate(0);
+            assert!(matches!(res, Err(EjdbError::InvalidValue(_))));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_doc_write_json_into() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut buf = XString::new();
+            let mut total = 0;
+            db.query("@c1/*")?.for_each(|doc| {
+                doc.write_json_into(&mut buf, None)?;
+                assert!(buf.starts_with("{\"a\":\"abc"));
+                total += buf.size();
+                Ok(())
+            })?;
+            assert!(total > 0);
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_filter_with_name() {
         catch(|| {
@@ -571,6 +1312,148 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_doc_to_pretty_string() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let json = db
+                .query("@c1/[c = 9]")?
+                .first(|doc| doc.to_pretty_string())?;
+            let json = json.unwrap();
+            assert!(json.contains("\n ") && json.contains("abc8"));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_write_json_array() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut buf = Vec::new();
+            let count = db.query("@c1/[c > 4]")?.write_json_array(&mut buf)?;
+            assert_eq!(count, 2);
+            let json = String::from_utf8(buf).unwrap();
+            assert!(json.starts_with("[{") && json.ends_with("}]"));
+            let jbl: JBL = json.parse()?;
+            assert_eq!(jbl.len(), 2);
+            let mut buf = Vec::new();
+            let count = db.query("@c1/[c > 100]")?.write_json_array(&mut buf)?;
+            assert_eq!(count, 0);
+            assert_eq!(buf, b"[]");
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_order_by() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut query = db.query("@c1/[c > :c]")?;
+            query.order_by("/c", false)?;
+            query.jql().set_i64("c", 2)?;
+            let values = query.to_vec(|doc| doc.to_jbl()?.get_i64("c"))?;
+            assert_eq!(values, vec![9, 5, 4, 3]);
+            let mut query = db.query_with_collection("/*", "c1")?.take(2);
+            query.order_by("/b", true)?;
+            let values = query.to_vec(|doc| Ok(doc.to_jbl()?.get_str("b")?.as_str().to_owned()))?;
+            assert_eq!(values, vec!["cde1", "cde2"]);
+            let mut query = db.query("@c1/* | skip 1")?;
+            let res = query.order_by("/a", true);
+            assert!(matches!(res, Err(EjdbError::JQLParseError { .. })));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_null_semantics() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            db.put("c1", "{\"a\":\"abc9\"}", Some(9))?;
+            let mut query = db.query("@c1/[c = :c]")?;
+            query.jql().set_null("c")?;
+            assert_eq!(query.to_vec(|doc| Ok(doc.id()))?, vec![2]);
+            let query = db.query("@c1/[c != 100]")?;
+            assert!(!query.to_vec(|doc| Ok(doc.id()))?.contains(&9));
+            assert_eq!(db.query("@c1/*")?.missing_ids("/c")?, vec![9]);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_set_str_in() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut query = db.query("@c1/[b in :statuses]")?;
+            query
+                .jql()
+                .set_str_in("statuses", &["cde1", "cde3", "cde9"])?;
+            let ids = query.to_vec(|doc| Ok(doc.id()))?;
+            assert_eq!(ids.len(), 3);
+            assert!(ids.contains(&1) && ids.contains(&3) && ids.contains(&5));
+            query.jql().set_str_in("statuses", &[])?;
+            assert_eq!(query.count()?, 0);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reset() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut query = db.query("@c1/[b = :v]")?;
+            query.jql().set_str("v", "cde1")?;
+            assert_eq!(query.to_vec(|doc| Ok(doc.id()))?, vec![1]);
+            query.reset(true, true).jql().set_str("v", "cde3")?;
+            assert_eq!(query.to_vec(|doc| Ok(doc.id()))?, vec![3]);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_apply_document() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut doc = JBL::new_object()?;
+            doc.set_prop("b", "x")?;
+            doc.set_prop("d", 1)?;
+            let mut query = db.query("@c1/=:id | apply :doc")?;
+            query.jql().set_i64("id", 3)?;
+            query.jql().set_document_jbl("doc", &doc)?;
+            query.exec()?;
+            let jbl = db.get("c1", 3)?;
+            assert_eq!(
+                jbl.to_string(),
+                "{\"a\":\"abc3\",\"b\":\"x\",\"c\":5,\"d\":1}"
+            );
+            let res = query.jql().set_document("doc", "1");
+            assert!(matches!(res, Err(EjdbError::UnexpectedType { .. })));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_ensure_bound() {
+        catch(|| {
+            let db = TestDb::new_with_seed()?;
+            let mut query = db.query("@c1/[c > :age] and /[a = :?]")?;
+            query.jql().set_i64("age", 8)?;
+            let res = query.ensure_bound();
+            assert!(matches!(res, Err(EjdbError::UnboundPlaceholder(_))));
+            query.jql().set_str(0, "abc8")?;
+            query.ensure_bound()?;
+            assert_eq!(query.count()?, 1);
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_filter_with_index() {
         catch(|| {
@@ -1,10 +1,26 @@
-use core::slice;
+use core::{
+    fmt, mem, ptr, slice,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering},
+};
 use ejdb2_sys as sys;
 
 pub use core::ffi::c_void;
 pub use libc::c_char;
+pub use libc::c_int;
 pub use libc::strlen;
 
+/// `va_list` argument as declared by the bindings of `IWLOG_FN`;
+/// on x86_64 SysV the array type decays to a pointer to its element,
+/// elsewhere it is passed as is. A mismatch fails to compile
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+type VaList = *mut sys::__va_list_tag;
+#[cfg(not(all(target_arch = "x86_64", not(windows))))]
+type VaList = sys::va_list;
+
+extern "C" {
+    fn vsnprintf(s: *mut c_char, n: usize, format: *const c_char, ap: VaList) -> c_int;
+}
+
 #[inline(always)]
 pub fn ejdb_version() -> (u32, u32, u32) {
     unsafe {
@@ -16,6 +32,68 @@ pub fn ejdb_version() -> (u32, u32, u32) {
     }
 }
 
+/// version of EJDB2 library, ordered as semver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+/// oldest EJDB2 version this crate is tested against
+pub const EJDB2_MIN_VERSION: Version = Version::new(2, 0, 59);
+
+impl Version {
+    #[inline]
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// version of linked EJDB2 library
+    #[inline]
+    pub fn runtime() -> Self {
+        ejdb_version().into()
+    }
+
+    /// true if same major version and not older than `min`
+    #[inline]
+    pub fn is_compatible_with(&self, min: &Version) -> bool {
+        self.major == min.major && self >= min
+    }
+}
+
+impl From<(u32, u32, u32)> for Version {
+    #[inline]
+    fn from((major, minor, patch): (u32, u32, u32)) -> Self {
+        Self::new(major, minor, patch)
+    }
+}
+
+impl fmt::Display for Version {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// panic if linked EJDB2 library is not compatible with
+/// `EJDB2_MIN_VERSION`; call it at startup to fail early
+#[inline]
+pub fn assert_version_compatible() {
+    let version = Version::runtime();
+    assert!(
+        version.is_compatible_with(&EJDB2_MIN_VERSION),
+        "incompatible EJDB2 library {}, requires {} or later {}.x",
+        version,
+        EJDB2_MIN_VERSION,
+        EJDB2_MIN_VERSION.major
+    );
+}
+
 #[inline]
 pub fn iwlog_ecode_explained<'a>(rc: u64) -> &'a str {
     let ptr = unsafe { sys::iwlog_ecode_explained(rc) };
@@ -27,12 +105,220 @@ pub fn iwlog_ecode_explained<'a>(rc: u64) -> &'a str {
     unsafe { core::str::from_utf8_unchecked(v) }
 }
 
+/// level of EJDB2 log messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl From<sys::iwlog_lvl> for LogLevel {
+    #[inline]
+    fn from(lvl: sys::iwlog_lvl) -> Self {
+        match lvl {
+            sys::iwlog_lvl::IWLOG_ERROR => LogLevel::Error,
+            sys::iwlog_lvl::IWLOG_WARN => LogLevel::Warn,
+            sys::iwlog_lvl::IWLOG_INFO => LogLevel::Info,
+            _ => LogLevel::Debug,
+        }
+    }
+}
+
+/// custom handler of EJDB2 log messages
+pub type LogHandler = fn(LogLevel, &str);
+
+/// max level + 1 of messages to log, 0 means off
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Debug as u8 + 1);
+static LOG_HANDLER: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// log messages up to given level, `None` to turn off;
+/// the setting is process-wide, it applies to all databases
+#[inline]
+pub fn set_log_level(level: Option<LogLevel>) {
+    let val = level.map_or(0, |v| v as u8 + 1);
+    LOG_LEVEL.store(val, Ordering::SeqCst);
+    install_log_fn();
+}
+
+/// route log messages to handler instead of stderr, `None` to restore;
+/// the handler is process-wide, it receives messages of all databases
+/// from any thread
+#[inline]
+pub fn set_log_handler(handler: Option<LogHandler>) {
+    let p = handler.map_or(ptr::null_mut(), |f| f as *mut ());
+    LOG_HANDLER.store(p, Ordering::SeqCst);
+    install_log_fn();
+}
+
+fn install_log_fn() {
+    unsafe {
+        // iwlog must be initialized first, it is a no-op if already done
+        sys::ejdb_init();
+        sys::iwlog_set_logfn(Some(log_fn), ptr::null_mut());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn log_fn(
+    out: *mut c_void,
+    locale: *mut c_void,
+    lvl: sys::iwlog_lvl,
+    ecode: u64,
+    errno_code: c_int,
+    werror_code: c_int,
+    file: *const c_char,
+    line: c_int,
+    ts: u64,
+    opts: *mut c_void,
+    fmt: *const c_char,
+    argp: VaList,
+) -> u64 {
+    let level = LogLevel::from(lvl);
+    if level as u8 >= LOG_LEVEL.load(Ordering::SeqCst) {
+        return 0;
+    }
+    let handler = LOG_HANDLER.load(Ordering::SeqCst);
+    if handler.is_null() {
+        return sys::iwlog_default_logfn(
+            out,
+            locale,
+            lvl,
+            ecode,
+            errno_code,
+            werror_code,
+            file,
+            line,
+            ts,
+            opts,
+            fmt,
+            argp,
+        );
+    }
+    // only `set_log_handler` stores to it, from a `LogHandler`
+    let handler: LogHandler = mem::transmute(handler);
+    let mut buf = LogBuf {
+        data: [0; LOG_BUF_SIZE],
+        len: 0,
+    };
+    if ecode != 0 {
+        let _ = fmt::Write::write_fmt(&mut buf, format_args!("{}: ", iwlog_ecode_explained(ecode)));
+    }
+    if !fmt.is_null() {
+        let rest = &mut buf.data[buf.len..];
+        let n = vsnprintf(rest.as_mut_ptr() as *mut c_char, rest.len(), fmt, argp);
+        if n > 0 {
+            // output is truncated to buffer size, without trailing NUL
+            buf.len += core::cmp::min(n as usize, rest.len() - 1);
+        }
+    }
+    let msg = buf.as_str();
+    let _ = crate::utils::catch_unwind(|| handler(level, msg));
+    0
+}
+
+const LOG_BUF_SIZE: usize = 1024;
+
+/// fixed buffer of log message
+struct LogBuf {
+    data: [u8; LOG_BUF_SIZE],
+    len: usize,
+}
+
+impl LogBuf {
+    #[inline]
+    fn as_str(&self) -> &str {
+        let bytes = &self.data[..self.len];
+        match core::str::from_utf8(bytes) {
+            Ok(s) => s,
+            // message truncated in the middle of a char
+            Err(e) => unsafe { core::str::from_utf8_unchecked(&bytes[..e.valid_up_to()]) },
+        }
+    }
+}
+
+impl fmt::Write for LogBuf {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // keep one byte for NUL of vsnprintf
+        let n = core::cmp::min(s.len(), LOG_BUF_SIZE - 1 - self.len);
+        self.data[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn test_ejdb_version() {
-        assert!(ejdb_version() == (2, 0, 59));
+        assert_eq!(Version::from(ejdb_version()), EJDB2_MIN_VERSION);
+        assert_eq!(Version::runtime().to_string(), "2.0.59");
+        assert_version_compatible();
+    }
+
+    #[test]
+    fn test_version_order() {
+        let v = Version::new(2, 0, 59);
+        assert!(v < Version::new(2, 0, 60));
+        assert!(v < Version::new(2, 1, 0));
+        assert!(v > Version::new(1, 9, 99));
+        assert!(Version::new(2, 1, 0).is_compatible_with(&v));
+        assert!(!Version::new(2, 0, 58).is_compatible_with(&v));
+        assert!(!Version::new(3, 0, 0).is_compatible_with(&v));
+    }
+
+    #[test]
+    fn test_log_buf() {
+        let mut buf = LogBuf {
+            data: [0; LOG_BUF_SIZE],
+            len: 0,
+        };
+        let _ = fmt::Write::write_str(&mut buf, "abc");
+        assert_eq!(buf.as_str(), "abc");
+        let long = "x".repeat(LOG_BUF_SIZE * 2);
+        let _ = fmt::Write::write_str(&mut buf, &long);
+        assert_eq!(buf.len, LOG_BUF_SIZE - 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_set_log_level() {
+        use std::sync::Mutex;
+        static MESSAGES: Mutex<Vec<(LogLevel, String)>> = Mutex::new(Vec::new());
+
+        fn log(lvl: sys::iwlog_lvl, msg: &str) {
+            let msg = std::ffi::CString::new(msg).unwrap();
+            unsafe {
+                sys::iwlog(lvl, 0, ptr::null(), 0, b"%s\0".as_ptr() as _, msg.as_ptr());
+            }
+        }
+
+        set_log_level(Some(LogLevel::Warn));
+        set_log_handler(Some(|level, msg| {
+            // other tests may log at the same time
+            if msg.starts_with("test_set_log_level") {
+                MESSAGES.lock().unwrap().push((level, msg.to_owned()));
+            }
+        }));
+        log(sys::iwlog_lvl::IWLOG_ERROR, "test_set_log_level error");
+        log(sys::iwlog_lvl::IWLOG_WARN, "test_set_log_level warn");
+        log(sys::iwlog_lvl::IWLOG_INFO, "test_set_log_level info");
+        log(sys::iwlog_lvl::IWLOG_DEBUG, "test_set_log_level debug");
+        set_log_handler(None);
+        set_log_level(Some(LogLevel::Debug));
+
+        let messages = MESSAGES.lock().unwrap();
+        assert_eq!(
+            *messages,
+            vec![
+                (LogLevel::Error, "test_set_log_level error".to_owned()),
+                (LogLevel::Warn, "test_set_log_level warn".to_owned()),
+            ]
+        );
+        assert!(LogLevel::Error < LogLevel::Warn);
     }
 }
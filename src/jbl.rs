@@ -1,9 +1,10 @@
-use core::{convert::TryFrom, fmt, ptr, slice, str::FromStr};
+use core::{cell::Cell, cmp::Ordering, convert::TryFrom, fmt, ptr, slice, str::FromStr};
 
 use crate::{
     ffi,
+    pool::Pool,
     printer::{self, AsJson, JsonPrinter},
-    utils::check_rc,
+    utils::{self, check_rc},
     xstr::StringPtr,
     xstr::XString,
     EjdbError, JsonPrintFlags, Result,
@@ -11,10 +12,17 @@ use crate::{
 use ejdb2_sys as sys;
 pub use sys::jbl_type_t as JBLType;
 
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::ffi::CString;
+
 ///binary JSON object
 pub struct JBL {
     handle: sys::JBL,
     writable: bool,
+    /// iterator holder of path lookups, created on first use
+    holder: Cell<sys::JBL>,
 }
 
 impl JBL {
@@ -24,9 +32,12 @@ impl JBL {
         let mut h: sys::JBL = ptr::null_mut();
         let rc = unsafe { sys::jbl_create_empty_array(&mut h) };
         check_rc(rc)?;
+        #[cfg(feature = "alloc-stats")]
+        crate::stats::JBLS.inc();
         Ok(Self {
             handle: h,
             writable: true,
+            holder: Cell::new(ptr::null_mut()),
         })
     }
     /// create empty object
@@ -35,16 +46,22 @@ impl JBL {
         let mut h: sys::JBL = ptr::null_mut();
         let rc = unsafe { sys::jbl_create_empty_object(&mut h) };
         check_rc(rc)?;
+        #[cfg(feature = "alloc-stats")]
+        crate::stats::JBLS.inc();
         Ok(Self {
             handle: h,
             writable: true,
+            holder: Cell::new(ptr::null_mut()),
         })
     }
     #[inline(always)]
     pub(crate) fn from_ptr(handle: *mut sys::_JBL) -> Self {
+        #[cfg(feature = "alloc-stats")]
+        crate::stats::JBLS.inc();
         Self {
             handle,
             writable: false,
+            holder: Cell::new(ptr::null_mut()),
         }
     }
 
@@ -54,6 +71,20 @@ impl JBL {
         let json = json.into();
         unsafe { Self::from_c_str(json.as_ptr()) }
     }
+    /// from JSON bytes, fails with `EjdbError::Utf8Error` for invalid UTF-8,
+    /// and with `EjdbError::InvalidValue` for a NUL byte other than the last one.
+    /// Note: data is parsed in place only if it ends with a NUL byte,
+    /// otherwise it is copied into a NUL terminated buffer, as EJDB2 requires
+    pub fn from_json_bytes(data: &[u8]) -> Result<Self> {
+        core::str::from_utf8(data)?;
+        match data.iter().position(|&b| b == 0) {
+            Some(pos) if pos + 1 == data.len() => unsafe {
+                Self::from_c_str(data.as_ptr() as *const _)
+            },
+            Some(_) => Err(EjdbError::InvalidValue("JSON contains NUL byte")),
+            None => Self::try_from(XString::from(data)),
+        }
+    }
     /// from JSON string
     #[inline]
     pub unsafe fn from_c_str(str_ptr: *const i8) -> Result<Self> {
@@ -62,7 +93,9 @@ impl JBL {
         // }
         let mut handle = ptr::null_mut();
         let rc = sys::jbl_from_json(&mut handle, str_ptr);
-        check_rc(rc)?;
+        if rc != 0 {
+            return Err(EjdbError::InvalidJson(rc));
+        }
         Ok(Self::from_ptr(handle))
     }
 
@@ -89,21 +122,23 @@ impl JBL {
         unsafe { sys::jbl_count(self.raw_ptr()) as usize }
     }
 
+    /// element count of array or property count of object, same as `count`
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.count()
+    }
+
+    /// true if array or object has no child, same as `count() == 0`
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
     /// append value if JBL is a JSON array; Note: only work if writable
     #[inline]
     pub fn append<'a, 'b>(&mut self, val: impl IntoJBLValue<'b>) -> Result<()> {
-        let val = val.into_value();
         let key: Option<&str> = None;
-        match val {
-            JBLValue::Null => self.set_null(key),
-            JBLValue::EmptyArray => self.set_empty_array(key),
-            JBLValue::EmptyObject => self.set_empty_object(key),
-            JBLValue::Boolean(v) => self.set_bool(key, v),
-            JBLValue::Float(v) => self.set_f64(key, v),
-            JBLValue::Integer(v) => self.set_i64(key, v),
-            JBLValue::Nested(v) => self.set_nested(key, v),
-            JBLValue::String(v) => self.set_str(key, v),
-        }
+        self.set_value(key, val.into_value())
     }
 
     /// set property if JBL is a JSON object; Note: only work if writable
@@ -113,8 +148,82 @@ impl JBL {
         key: impl Into<StringPtr<'a>>,
         val: impl IntoJBLValue<'b>,
     ) -> Result<()> {
-        let val = val.into_value();
-        let key = Some(key);
+        self.set_value(Some(key), val.into_value())
+    }
+
+    /// deep copy
+    #[inline]
+    pub(crate) fn try_clone(&self) -> Result<JBL> {
+        let mut h = ptr::null_mut();
+        let rc = unsafe { sys::jbl_clone(self.raw_ptr(), &mut h) };
+        check_rc(rc)?;
+        Ok(Self::from_ptr(h))
+    }
+
+    /// builder of JSON object
+    #[inline]
+    pub fn object_builder() -> Result<JBLObjectBuilder> {
+        JBLObjectBuilder::new()
+    }
+
+    /// create JSON array from values
+    #[inline]
+    pub fn from_values<'a, T, I>(values: I) -> Result<Self>
+    where
+        T: IntoJBLValue<'a>,
+        I: IntoIterator<Item = T>,
+    {
+        let mut jbl = Self::new_array()?;
+        for v in values {
+            jbl.append(v)?;
+        }
+        Ok(jbl)
+    }
+
+    /// create JSON array from values, same as `from_values`
+    #[inline]
+    pub fn array_from<'a, T, I>(values: I) -> Result<Self>
+    where
+        T: IntoJBLValue<'a>,
+        I: IntoIterator<Item = T>,
+    {
+        Self::from_values(values)
+    }
+
+    /// create JSON object from key-value pairs; for duplicate keys
+    /// the last value wins and takes the position of the last pair
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn object_from<'a, 'b, K, V, I>(pairs: I) -> Result<Self>
+    where
+        K: Into<StringPtr<'a>>,
+        V: IntoJBLValue<'b>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        // EJDB2 appends duplicate keys as is
+        let pairs: Vec<(XString, V)> = pairs
+            .into_iter()
+            .map(|(k, v)| (k.into().to_owned(), v))
+            .collect();
+        let dup: Vec<bool> = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (key, _))| pairs[i + 1..].iter().any(|(k, _)| k == key))
+            .collect();
+        let mut jbl = Self::new_object()?;
+        for ((key, val), dup) in pairs.into_iter().zip(dup) {
+            if !dup {
+                jbl.set_prop(&key, val)?;
+            }
+        }
+        Ok(jbl)
+    }
+
+    #[inline]
+    fn set_value<'a, 'b, K: Into<StringPtr<'a>>>(
+        &mut self,
+        key: Option<K>,
+        val: JBLValue<'b>,
+    ) -> Result<()> {
         match val {
             JBLValue::Null => self.set_null(key),
             JBLValue::EmptyArray => self.set_empty_array(key),
@@ -124,6 +233,11 @@ impl JBL {
             JBLValue::Integer(v) => self.set_i64(key, v),
             JBLValue::Nested(v) => self.set_nested(key, v),
             JBLValue::String(v) => self.set_str(key, v),
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            JBLValue::Array(items) => {
+                let v = Self::from_values(items)?;
+                self.set_nested(key, v)
+            }
         }
     }
 
@@ -142,6 +256,7 @@ impl JBL {
     /// set object property
     #[inline]
     fn set_f64<'a, K: Into<StringPtr<'a>>>(&mut self, key: Option<K>, val: f64) -> Result<()> {
+        let val = utils::check_finite(val)?;
         let rc = match key {
             Some(key) => unsafe {
                 let key = key.into();
@@ -244,6 +359,40 @@ impl JBL {
         check_rc(rc)
     }
 
+    /// apply JSON merge patch (rfc7396) from another JBL;
+    /// Note: only work if writable
+    #[inline]
+    pub fn merge_jbl(&mut self, other: &JBL) -> Result<()> {
+        let rc = unsafe { sys::jbl_merge_patch_jbl(self.raw_ptr(), other.raw_ptr()) };
+        check_rc(rc)
+    }
+    /// apply JSON patch (rfc6902) or JSON merge patch (rfc7396) from another JBL,
+    /// depending on whether patch is an array or an object
+    #[inline]
+    pub fn patch_jbl(&mut self, patch: &JBL) -> Result<()> {
+        let pool = Pool::new()?;
+        let mut root = ptr::null_mut();
+        let mut patch_node = ptr::null_mut();
+        let rc = unsafe {
+            let rc = sys::jbl_to_node(self.raw_ptr(), &mut root, true, pool.as_mut_ptr());
+            check_rc(rc)?;
+            let rc = sys::jbl_to_node(patch.raw_ptr(), &mut patch_node, true, pool.as_mut_ptr());
+            check_rc(rc)?;
+            sys::jbn_patch_auto(root, patch_node, pool.as_mut_ptr())
+        };
+        check_rc(rc)?;
+        let is_array = unsafe { (*root).type_ == JBLType::JBV_ARRAY };
+        let mut jbl = if is_array {
+            JBL::new_array()?
+        } else {
+            JBL::new_object()?
+        };
+        let rc = unsafe { sys::jbl_fill_from_node(jbl.raw_ptr(), root) };
+        check_rc(rc)?;
+        core::mem::swap(&mut self.handle, &mut jbl.handle);
+        Ok(())
+    }
+
     /// get property if JBL is a JSON object;
     #[inline]
     pub fn get_bool<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<bool> {
@@ -267,6 +416,35 @@ impl JBL {
         check_rc(rc).and(Ok(val))
     }
     /// get property if JBL is a JSON object;
+    /// fails with `EjdbError::OutOfRange` if value does not fit
+    #[inline]
+    pub fn get_i32<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<i32> {
+        self.get_int(key)
+    }
+    /// get property if JBL is a JSON object;
+    /// fails with `EjdbError::OutOfRange` if value does not fit
+    #[inline]
+    pub fn get_u16<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<u16> {
+        self.get_int(key)
+    }
+    /// get property if JBL is a JSON object;
+    /// fails with `EjdbError::OutOfRange` if value does not fit
+    #[inline]
+    pub fn get_u32<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<u32> {
+        self.get_int(key)
+    }
+    /// get property if JBL is a JSON object;
+    /// fails with `EjdbError::OutOfRange` if value is negative
+    #[inline]
+    pub fn get_u64<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<u64> {
+        self.get_int(key)
+    }
+    #[inline]
+    fn get_int<'a, T: TryFrom<i64>>(&self, key: impl Into<StringPtr<'a>>) -> Result<T> {
+        let val = self.get_i64(key)?;
+        T::try_from(val).map_err(|_| EjdbError::OutOfRange(val))
+    }
+    /// get property if JBL is a JSON object;
     #[inline]
     pub fn get_f64<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<f64> {
         let key = key.into();
@@ -288,10 +466,185 @@ impl JBL {
     }
     /// get property if JBL is a JSON object;
     #[inline]
-    pub fn get_type<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<JBLType> {
+    pub fn get_type<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<JsonType> {
         let key = key.into();
         let res = unsafe { sys::jbl_object_get_type(self.raw_ptr(), key.as_ptr()) };
-        Ok(res)
+        Ok(res.into())
+    }
+
+    /// get number at rfc6901 path, floats are truncated;
+    /// fails with `EjdbError::UnexpectedType` for other values
+    #[inline]
+    pub fn get_i64_at<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<i64> {
+        self.scalar_at(path, JsonType::Integer, |v| v.as_i64())
+    }
+    /// get number at rfc6901 path;
+    /// fails with `EjdbError::UnexpectedType` for other values
+    #[inline]
+    pub fn get_f64_at<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<f64> {
+        self.scalar_at(path, JsonType::Float, |v| v.as_f64())
+    }
+    /// get boolean at rfc6901 path;
+    /// fails with `EjdbError::UnexpectedType` for other values
+    #[inline]
+    pub fn get_bool_at<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<bool> {
+        self.scalar_at(path, JsonType::Boolean, |v| v.as_i64() != 0)
+    }
+    /// get string at rfc6901 path;
+    /// fails with `EjdbError::UnexpectedType` for other values
+    #[inline]
+    pub fn get_str_at<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<XString> {
+        self.scalar_at(path, JsonType::String, |v| v.str_bytes().into())
+    }
+    /// read value at path, integers and floats are interchangeable
+    #[inline]
+    fn scalar_at<'a, T>(
+        &self,
+        path: impl Into<StringPtr<'a>>,
+        expected: JsonType,
+        f: impl FnOnce(&JBL) -> T,
+    ) -> Result<T> {
+        self.lookup(path, |v| {
+            let v = match v {
+                Some(v) => v,
+                None => return Err(EjdbError::Generic(PATH_NOTFOUND)),
+            };
+            let actual = v.value_type();
+            let numeric = |t| t == JsonType::Integer || t == JsonType::Float;
+            if actual == expected || (numeric(actual) && numeric(expected)) {
+                Ok(f(v))
+            } else {
+                Err(EjdbError::UnexpectedType { expected, actual })
+            }
+        })
+    }
+
+    /// type of value at rfc6901 path, `JsonType::None` if path is not present;
+    /// fails for malformed path
+    #[inline]
+    pub fn type_at<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<JsonType> {
+        self.lookup(path, |v| Ok(v.map_or(JsonType::None, |v| v.value_type())))
+    }
+
+    /// walk rfc6901 path in place, `f` gets `None` if path is not present;
+    /// unlike `jbl_at`, neither the pointer nor the value is allocated,
+    /// the value is read into the iterator holder kept by this JBL
+    fn lookup<'a, R>(
+        &self,
+        path: impl Into<StringPtr<'a>>,
+        f: impl FnOnce(Option<&JBL>) -> Result<R>,
+    ) -> Result<R> {
+        let path = path.into();
+        let path = unsafe {
+            let data = path.as_ptr();
+            slice::from_raw_parts(data as *const u8, ffi::strlen(data))
+        };
+        if path.is_empty() {
+            return f(Some(self));
+        }
+        if path[0] != b'/' {
+            return Err(EjdbError::Generic(JSON_POINTER));
+        }
+        let holder = self.holder()?;
+        let mut cur = self.raw_ptr();
+        for seg in path[1..].split(|&b| b == b'/') {
+            if !is_valid_segment(seg) {
+                return Err(EjdbError::Generic(JSON_POINTER));
+            }
+            let mut iter: sys::JBL_iterator = unsafe { core::mem::zeroed() };
+            // iterator refers to data of the document, so holder can be refilled
+            let rc = unsafe { sys::jbl_iterator_init(cur, &mut iter) };
+            check_rc(rc)?;
+            let index = match unsafe { sys::jbl_type(cur) }.into() {
+                JsonType::Array => match parse_index(seg) {
+                    Some(i) => Some(i),
+                    None => return f(None),
+                },
+                _ => None,
+            };
+            let mut pos = 0;
+            let mut key = ptr::null_mut();
+            let mut klen = 0;
+            let found = loop {
+                if !unsafe { sys::jbl_iterator_next(&mut iter, holder, &mut key, &mut klen) } {
+                    break false;
+                }
+                let hit = match index {
+                    Some(i) => i == pos,
+                    None if key.is_null() => false,
+                    None => {
+                        let k = unsafe { slice::from_raw_parts(key as *const u8, klen as usize) };
+                        segment_matches(seg, k)
+                    }
+                };
+                if hit {
+                    break true;
+                }
+                pos += 1;
+            };
+            if !found {
+                return f(None);
+            }
+            cur = holder;
+        }
+        let value = core::mem::ManuallyDrop::new(Self {
+            handle: holder,
+            writable: false,
+            holder: Cell::new(ptr::null_mut()),
+        });
+        f(Some(&value))
+    }
+
+    /// iterator holder of `lookup`, kept until drop
+    #[inline]
+    fn holder(&self) -> Result<sys::JBL> {
+        let mut h = self.holder.get();
+        if h.is_null() {
+            let rc = unsafe { sys::jbl_create_iterator_holder(&mut h) };
+            check_rc(rc)?;
+            self.holder.set(h);
+        }
+        Ok(h)
+    }
+
+    #[inline]
+    fn release_holder(&self) {
+        let mut h = self.holder.replace(ptr::null_mut());
+        if !h.is_null() {
+            unsafe { sys::jbl_destroy_iterator_holder(&mut h) };
+        }
+    }
+
+    /// type of this JBL value
+    #[inline(always)]
+    pub fn value_type(&self) -> JsonType {
+        unsafe { sys::jbl_type(self.raw_ptr()) }.into()
+    }
+
+    /// get property value as JBL if JBL is a JSON object;
+    /// unlike `find`, key is not interpreted as path
+    #[inline]
+    pub fn get<'a>(&self, key: impl Into<StringPtr<'a>>) -> Result<JBL> {
+        let key = key.into().to_owned();
+        let path = key_pointer(key.as_str());
+        self.find(&path)
+    }
+
+    /// get element if JBL is a JSON array;
+    /// returns `EjdbError::UnexpectedType` for other values
+    #[inline]
+    pub fn at(&self, index: usize) -> Result<JBL> {
+        let actual = self.value_type();
+        if actual != JsonType::Array {
+            return Err(EjdbError::UnexpectedType {
+                expected: JsonType::Array,
+                actual,
+            });
+        }
+        use core::fmt::Write;
+        let mut path = XString::new();
+        let _ = write!(path, "/{}", index);
+        self.find(&path)
     }
 
     /// find value by rfc6901 path
@@ -304,6 +657,129 @@ impl JBL {
         Ok(Self::from_ptr(h))
     }
 
+    /// find value by rfc6901 path, `None` if path is not present;
+    /// fails for malformed path
+    #[inline]
+    pub fn find_opt<'a>(&self, path: impl Into<StringPtr<'a>>) -> Result<Option<JBL>> {
+        match self.find(path) {
+            Ok(v) => Ok(Some(v)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// visit child elements in document order;
+    /// key is `None` for array elements
+    pub(crate) fn for_each<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(Option<&[u8]>, &JBL) -> Result<()>,
+    {
+        let mut iter: sys::JBL_iterator = unsafe { core::mem::zeroed() };
+        let rc = unsafe { sys::jbl_iterator_init(self.raw_ptr(), &mut iter) };
+        check_rc(rc)?;
+        let mut h = ptr::null_mut();
+        let rc = unsafe { sys::jbl_create_iterator_holder(&mut h) };
+        check_rc(rc)?;
+        // holder is released by jbl_destroy_iterator_holder
+        let holder = core::mem::ManuallyDrop::new(Self {
+            handle: h,
+            writable: false,
+            holder: Cell::new(ptr::null_mut()),
+        });
+        let mut res = Ok(());
+        let mut key = ptr::null_mut();
+        let mut klen = 0;
+        while unsafe { sys::jbl_iterator_next(&mut iter, holder.raw_ptr(), &mut key, &mut klen) } {
+            let k = if key.is_null() {
+                None
+            } else {
+                Some(unsafe { slice::from_raw_parts(key as *const u8, klen as usize) })
+            };
+            res = f(k, &holder);
+            if res.is_err() {
+                break;
+            }
+        }
+        holder.release_holder();
+        unsafe { sys::jbl_destroy_iterator_holder(&mut h) };
+        res
+    }
+
+    /// borrowed view of root value for pattern matching;
+    /// fails with `EjdbError::Utf8Error` for string not valid UTF-8
+    #[inline]
+    pub fn value(&self) -> Result<JBLValueRef<'_>> {
+        let v = match self.value_type() {
+            JsonType::None | JsonType::Null => JBLValueRef::Null,
+            JsonType::Boolean => JBLValueRef::Bool(self.as_i64() != 0),
+            JsonType::Integer => JBLValueRef::Int(self.as_i64()),
+            JsonType::Float => JBLValueRef::Float(self.as_f64()),
+            JsonType::String => JBLValueRef::Str(self.try_as_str()?),
+            JsonType::Array => JBLValueRef::Array(self),
+            JsonType::Object => JBLValueRef::Object(self),
+        };
+        Ok(v)
+    }
+
+    /// compare two scalar values of the same kind, `None` for
+    /// incomparable values, e.g. string with number, arrays or objects;
+    /// - integers are compared exactly, while an integer and a float are
+    ///   compared as f64, so integers beyond 2^53 may lose precision
+    /// - strings are compared by bytes, which is code point order
+    /// - `false` is less than `true`, `null` equals `null`
+    pub fn cmp_value(&self, other: &JBL) -> Option<Ordering> {
+        use JBLValueRef::*;
+        // strings are compared as is, without UTF-8 validation
+        if self.value_type() == JsonType::String && other.value_type() == JsonType::String {
+            return Some(self.str_bytes().cmp(other.str_bytes()));
+        }
+        match (self.value().ok()?, other.value().ok()?) {
+            (Null, Null) => Some(Ordering::Equal),
+            (Bool(a), Bool(b)) => Some(a.cmp(&b)),
+            (Int(a), Int(b)) => Some(a.cmp(&b)),
+            (Int(a), Float(b)) => (a as f64).partial_cmp(&b),
+            (Float(a), Int(b)) => a.partial_cmp(&(b as f64)),
+            (Float(a), Float(b)) => a.partial_cmp(&b),
+            _ => None,
+        }
+    }
+
+    /// visit elements of array or properties of object in document order,
+    /// with key of property; values are only valid within the callback,
+    /// as EJDB2 iterates with a single holder
+    #[inline]
+    pub fn visit_values<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(Option<&str>, JBLValueRef<'_>) -> Result<()>,
+    {
+        self.for_each(|key, item| {
+            let key = match key {
+                Some(k) => Some(core::str::from_utf8(k)?),
+                None => None,
+            };
+            f(key, item.value()?)
+        })
+    }
+
+    /// property names of JSON object in document order, which is insertion order;
+    /// the order is kept as is when document is stored and loaded, while
+    /// `merge`/`patch` append new properties at the end;
+    /// empty for arrays and other values
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::with_capacity(self.count());
+        if self.value_type() != JsonType::Object {
+            return Ok(keys);
+        }
+        self.for_each(|key, _| {
+            if let Some(key) = key {
+                keys.push(core::str::from_utf8(key)?.into());
+            }
+            Ok(())
+        })?;
+        Ok(keys)
+    }
+
     /// convert to f64, returns 0 if value cannot be converted
     #[inline(always)]
     pub fn as_f64(&self) -> f64 {
@@ -322,17 +798,68 @@ impl JBL {
         unsafe { sys::jbl_get_i32(self.raw_ptr()) }
     }
 
-    /// convert to str
+    /// convert to str without UTF-8 validation;
+    /// the result is undefined behavior if value is not valid UTF-8,
+    /// use `try_as_str` for values from untrusted or binary data
     #[inline]
     pub fn as_str(&self) -> &str {
+        unsafe { core::str::from_utf8_unchecked(self.str_bytes()) }
+    }
+
+    /// convert to str, fails with `EjdbError::Utf8Error` if value is not valid UTF-8
+    #[inline]
+    pub fn try_as_str(&self) -> Result<&str> {
+        let s = core::str::from_utf8(self.str_bytes())?;
+        Ok(s)
+    }
+
+    #[inline]
+    fn str_bytes(&self) -> &[u8] {
         unsafe {
             let data = sys::jbl_get_str(self.raw_ptr());
+            if data.is_null() {
+                return &[];
+            }
             let len = ffi::strlen(data);
-            let buf = slice::from_raw_parts(data as *const u8, len);
-            core::str::from_utf8_unchecked(buf)
+            slice::from_raw_parts(data as *const u8, len)
         }
     }
 
+    /// to indented JSON string, non-ASCII chars are escaped
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn to_pretty_string(&self) -> Result<String> {
+        self.as_json(Some(
+            JsonPrintFlags::PRINT_PRETTY | JsonPrintFlags::PRINT_CODEPOINTS,
+        ))
+    }
+
+    /// to NUL-terminated JSON for foreign code, fails if the output
+    /// contains interior NUL
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn as_json_cstring(&self, flag: Option<JsonPrintFlags>) -> Result<CString> {
+        let buf: Vec<u8> = self.as_json(flag)?;
+        Ok(CString::new(buf)?)
+    }
+
+    /// print json into buffer, which is cleared first;
+    /// reuse buffer to avoid allocation for each call of `as_json()`
+    #[inline]
+    pub fn write_json_into(&self, buf: &mut XString, flag: Option<JsonPrintFlags>) -> Result<()> {
+        buf.clear();
+        let flag = flag.unwrap_or(JsonPrintFlags::PRINT_CODEPOINTS);
+        let rc = unsafe {
+            sys::jbl_as_json(
+                self.raw_ptr(),
+                Some(sys::jbl_xstr_json_printer),
+                buf.as_mut_ptr() as *mut _,
+                flag.bits,
+            )
+        };
+        check_rc(rc)
+    }
+
     /// print json to writer
     #[inline]
     pub fn print<T: JsonPrinter>(
@@ -343,6 +870,157 @@ impl JBL {
         let flag = flag.unwrap_or(JsonPrintFlags::PRINT_CODEPOINTS);
         printer::jbl_print_json(self.raw_ptr(), target, flag)
     }
+
+    /// print compact JSON, with objects and arrays nested deeper than
+    /// `max_depth` replaced by `{...}` and `[...]`, e.g. for logging;
+    /// rendered by this crate on top of the iterator, not by the native
+    /// printer, which only prints scalar values; `PRINT_PRETTY` is ignored
+    /// and non-ASCII chars of keys are not escaped
+    pub fn print_truncated<T: JsonPrinter>(
+        &self,
+        target: &mut T,
+        flag: Option<JsonPrintFlags>,
+        max_depth: usize,
+    ) -> Result<()> {
+        let mut flag = flag.unwrap_or(JsonPrintFlags::PRINT_CODEPOINTS);
+        flag.remove(JsonPrintFlags::PRINT_PRETTY);
+        self.print_depth(target, flag, max_depth)
+    }
+
+    fn print_depth<T: JsonPrinter>(
+        &self,
+        target: &mut T,
+        flag: JsonPrintFlags,
+        depth: usize,
+    ) -> Result<()> {
+        let (open, close) = match self.value_type() {
+            JsonType::Object => (b'{', b'}'),
+            JsonType::Array => (b'[', b']'),
+            _ => return printer::jbl_print_json(self.raw_ptr(), target, flag),
+        };
+        if self.is_empty() {
+            return target.print(&[open, close], 1);
+        }
+        if depth == 0 {
+            return target.print(&[open, b'.', b'.', b'.', close], 1);
+        }
+        target.print(&[open], 1)?;
+        let mut first = true;
+        self.for_each(|key, item| {
+            if !first {
+                target.print(b",", 1)?;
+            }
+            first = false;
+            if let Some(key) = key {
+                printer::print_json_key(target, key)?;
+            }
+            item.print_depth(target, flag, depth - 1)
+        })?;
+        target.print(&[close], 1)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl JBL {
+    /// convert into serde JSON value by walking the document, without
+    /// printing and parsing JSON text; integers stay i64, floats f64.
+    /// fails with `EjdbError::Utf8Error` for strings or keys not valid UTF-8
+    pub fn to_json_value(&self) -> Result<serde_json::Value> {
+        use serde_json::Value;
+        let value = match self.value()? {
+            JBLValueRef::Null => Value::Null,
+            JBLValueRef::Bool(v) => Value::Bool(v),
+            JBLValueRef::Int(v) => Value::from(v),
+            JBLValueRef::Float(v) => {
+                serde_json::Number::from_f64(v).map_or(Value::Null, Value::Number)
+            }
+            JBLValueRef::Str(s) => Value::String(s.into()),
+            JBLValueRef::Array(_) => {
+                let mut items = Vec::with_capacity(self.count());
+                self.for_each(|_, item| {
+                    items.push(item.to_json_value()?);
+                    Ok(())
+                })?;
+                Value::Array(items)
+            }
+            JBLValueRef::Object(_) => {
+                let mut map = serde_json::Map::with_capacity(self.count());
+                self.for_each(|key, item| {
+                    let key = core::str::from_utf8(key.unwrap_or_default())?;
+                    map.insert(key.into(), item.to_json_value()?);
+                    Ok(())
+                })?;
+                Value::Object(map)
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<&JBL> for serde_json::Value {
+    type Error = EjdbError;
+    #[inline]
+    fn try_from(jbl: &JBL) -> Result<Self> {
+        jbl.to_json_value()
+    }
+}
+
+const JSON_POINTER: u64 = sys::jbl_ecode_t::JBL_ERROR_JSON_POINTER as u64;
+const PATH_NOTFOUND: u64 = sys::jbl_ecode_t::JBL_ERROR_PATH_NOTFOUND as u64;
+
+/// `~` of rfc6901 path segment must be escape of `~0` or `~1`
+fn is_valid_segment(seg: &[u8]) -> bool {
+    let mut iter = seg.iter();
+    while let Some(&b) = iter.next() {
+        if b == b'~' && !matches!(iter.next(), Some(b'0') | Some(b'1')) {
+            return false;
+        }
+    }
+    true
+}
+
+/// compare escaped rfc6901 path segment with raw object key
+fn segment_matches(seg: &[u8], key: &[u8]) -> bool {
+    let mut seg = seg.iter();
+    let mut key = key.iter();
+    loop {
+        let c = match seg.next() {
+            Some(b'~') => match seg.next() {
+                Some(b'0') => b'~',
+                _ => b'/',
+            },
+            Some(&c) => c,
+            None => return key.next().is_none(),
+        };
+        if key.next() != Some(&c) {
+            return false;
+        }
+    }
+}
+
+/// array index of rfc6901 path segment, only decimal digits are allowed
+fn parse_index(seg: &[u8]) -> Option<usize> {
+    if seg.is_empty() || !seg.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    core::str::from_utf8(seg).ok()?.parse().ok()
+}
+
+/// rfc6901 pointer of single key
+#[inline]
+pub(crate) fn key_pointer(key: &str) -> XString {
+    let mut path = XString::new_with_size(key.len() + 1);
+    path.push("/");
+    let mut buf = [0_u8; 4];
+    for c in key.chars() {
+        match c {
+            '~' => path.push("~0"),
+            '/' => path.push("~1"),
+            _ => path.push(c.encode_utf8(&mut buf)),
+        };
+    }
+    path
 }
 
 impl FromStr for JBL {
@@ -362,22 +1040,21 @@ impl TryFrom<XString> for JBL {
     }
 }
 
+impl TryFrom<&[u8]> for JBL {
+    type Error = EjdbError;
+    #[inline]
+    fn try_from(data: &[u8]) -> Result<Self> {
+        Self::from_json_bytes(data)
+    }
+}
+
 impl AsJson<XString> for JBL {
     /// more efficient than use print() for XString
     #[inline]
     fn as_json(&self, flag: Option<JsonPrintFlags>) -> Result<XString> {
-        let flag = flag.unwrap_or(JsonPrintFlags::PRINT_CODEPOINTS);
         let size = self.size() * 2;
-        let data = XString::new_with_size(size);
-        let rc = unsafe {
-            sys::jbl_as_json(
-                self.raw_ptr(),
-                Some(sys::jbl_xstr_json_printer),
-                data.as_ptr() as *mut _,
-                flag.bits,
-            )
-        };
-        check_rc(rc).and(Ok(data))
+        let mut data = XString::new_with_size(size);
+        self.write_json_into(&mut data, flag).and(Ok(data))
     }
 }
 #[cfg(any(feature = "std", feature = "alloc"))]
@@ -400,10 +1077,15 @@ impl AsJson<String> for JBL {
 }
 
 impl fmt::Display for JBL {
+    /// compact JSON, or pretty JSON with `{:#}`
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s: XString = self.as_json(None).map_err(|_e| fmt::Error)?;
-        write!(f, "{}", s)
+        let flag = printer::fmt_flags(f);
+        let res: Result<XString> = self.as_json(Some(flag));
+        match res {
+            Ok(s) => write!(f, "{}", s),
+            Err(_) => write!(f, "{}", printer::INVALID_JSON),
+        }
     }
 }
 impl fmt::Debug for JBL {
@@ -416,9 +1098,90 @@ impl fmt::Debug for JBL {
 impl Drop for JBL {
     #[inline(always)]
     fn drop(&mut self) {
+        self.release_holder();
         unsafe {
             sys::jbl_destroy(&mut self.handle);
         }
+        #[cfg(feature = "alloc-stats")]
+        crate::stats::JBLS.dec();
+    }
+}
+
+/// builder of JSON object, which allows chained properties,
+/// e.g. `builder.prop("name", "lily")?.prop("age", 18)?;`
+pub struct JBLObjectBuilder {
+    inner: JBL,
+}
+
+impl JBLObjectBuilder {
+    /// builder of empty object
+    #[inline]
+    pub fn new() -> Result<Self> {
+        let inner = JBL::new_object()?;
+        Ok(Self { inner })
+    }
+
+    /// set property
+    #[inline]
+    pub fn prop<'a, 'b>(
+        &mut self,
+        key: impl Into<StringPtr<'a>>,
+        val: impl IntoJBLValue<'b>,
+    ) -> Result<&mut Self> {
+        self.inner.set_prop(key, val)?;
+        Ok(self)
+    }
+
+    /// finish building
+    #[inline(always)]
+    pub fn build(self) -> JBL {
+        self.inner
+    }
+}
+
+/// JSON value type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JsonType {
+    /// no value, e.g. missing property
+    None,
+    Null,
+    Boolean,
+    Integer,
+    Float,
+    String,
+    Object,
+    Array,
+}
+
+impl From<JBLType> for JsonType {
+    #[inline]
+    fn from(t: JBLType) -> Self {
+        match t {
+            JBLType::JBV_NONE => JsonType::None,
+            JBLType::JBV_NULL => JsonType::Null,
+            JBLType::JBV_BOOL => JsonType::Boolean,
+            JBLType::JBV_I64 => JsonType::Integer,
+            JBLType::JBV_F64 => JsonType::Float,
+            JBLType::JBV_STR => JsonType::String,
+            JBLType::JBV_OBJECT => JsonType::Object,
+            JBLType::JBV_ARRAY => JsonType::Array,
+        }
+    }
+}
+
+impl From<JsonType> for JBLType {
+    #[inline]
+    fn from(t: JsonType) -> Self {
+        match t {
+            JsonType::None => JBLType::JBV_NONE,
+            JsonType::Null => JBLType::JBV_NULL,
+            JsonType::Boolean => JBLType::JBV_BOOL,
+            JsonType::Integer => JBLType::JBV_I64,
+            JsonType::Float => JBLType::JBV_F64,
+            JsonType::String => JBLType::JBV_STR,
+            JsonType::Object => JBLType::JBV_OBJECT,
+            JsonType::Array => JBLType::JBV_ARRAY,
+        }
     }
 }
 
@@ -431,7 +1194,26 @@ pub enum JBLValue<'a> {
     String(StringPtr<'a>),
     Boolean(bool),
     Nested(JBL),
+    /// JSON array of values
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    Array(Vec<JBLValue<'a>>),
+}
+/// JBL borrowed from a parent or from iteration
+pub type JBLRef<'a> = &'a JBL;
+
+/// borrowed JSON value for reading, see [`JBL::value`];
+/// `JBLValue` is for construction
+#[derive(Debug, Clone, Copy)]
+pub enum JBLValueRef<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(&'a str),
+    Array(JBLRef<'a>),
+    Object(JBLRef<'a>),
 }
+
 pub trait IntoJBLValue<'a> {
     fn into_value(self) -> JBLValue<'a>;
 }
@@ -516,6 +1298,32 @@ impl<'a> IntoJBLValue<'a> for &'a str {
         JBLValue::String(self.into())
     }
 }
+
+impl<'a> IntoJBLValue<'a> for &&'a str {
+    #[inline(always)]
+    fn into_value(self) -> JBLValue<'a> {
+        JBLValue::String((*self).into())
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl<'a, T: IntoJBLValue<'a>> IntoJBLValue<'a> for Vec<T> {
+    #[inline]
+    fn into_value(self) -> JBLValue<'a> {
+        JBLValue::Array(self.into_iter().map(|v| v.into_value()).collect())
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl<'a, T> IntoJBLValue<'a> for &'a [T]
+where
+    &'a T: IntoJBLValue<'a>,
+{
+    #[inline]
+    fn into_value(self) -> JBLValue<'a> {
+        JBLValue::Array(self.iter().map(|v| v.into_value()).collect())
+    }
+}
 #[cfg(any(feature = "std", feature = "alloc"))]
 impl<'a> IntoJBLValue<'a> for String {
     #[inline(always)]
@@ -567,7 +1375,35 @@ mod test {
         assert_eq!(b, "OK");
 
         let t = obj.get_type("c").unwrap();
-        assert_eq!(t, JBLType::JBV_NULL);
+        assert_eq!(t, JsonType::Null);
+        assert_eq!(obj.get_type("d").unwrap(), JsonType::None);
+        assert_eq!(obj.value_type(), JsonType::Object);
+    }
+
+    #[test]
+    fn test_from_json_bytes() {
+        unsafe {
+            let rc = sys::jbl_init();
+            check_rc(rc).unwrap();
+        }
+
+        let data: &[u8] = b"{\"a\":1,\"b\":\"OK\"}";
+        let obj = JBL::from_json_bytes(data).unwrap();
+        assert_eq!(obj.get_i64("a").unwrap(), 1);
+        assert_eq!(obj.get_str("b").unwrap(), "OK");
+
+        let obj = JBL::try_from(&b"[1,2]\0"[..]).unwrap();
+        assert_eq!(obj.value_type(), JsonType::Array);
+
+        match JBL::from_json_bytes(b"{\"a\":\"\xff\"}") {
+            Err(EjdbError::Utf8Error(_)) => {}
+            _ => panic!("expect Utf8Error"),
+        }
+
+        match JBL::from_json_bytes(b"{}\0garbage") {
+            Err(EjdbError::InvalidValue(_)) => {}
+            _ => panic!("expect InvalidValue"),
+        }
     }
 
     #[test]
@@ -599,4 +1435,353 @@ mod test {
         let res: String = jbl.as_json(None).unwrap();
         assert_eq!(res, json);
     }
+
+    #[test]
+    fn test_array_value() {
+        let mut jbl = JBL::new_object().unwrap();
+        jbl.set_prop("a", vec!["x", "y"]).unwrap();
+        jbl.set_prop("b", Vec::<i64>::new()).unwrap();
+        jbl.set_prop("c", vec![vec![1, 2], vec![3]]).unwrap();
+        jbl.set_prop("d", &[true, false][..]).unwrap();
+        assert_eq!(
+            jbl.to_string(),
+            "{\"a\":[\"x\",\"y\"],\"b\":[],\"c\":[[1,2],[3]],\"d\":[true,false]}"
+        );
+        let arr = JBL::from_values(&["x", "y"]).unwrap();
+        assert_eq!(arr.to_string(), "[\"x\",\"y\"]");
+    }
+
+    #[test]
+    fn test_object_builder() {
+        let mut builder = JBL::object_builder().unwrap();
+        let nested = JBL::from_values(&[1, 2]).unwrap();
+        builder
+            .prop("a", "x")
+            .and_then(|b| b.prop("b", 1))
+            .and_then(|b| b.prop("c", nested))
+            .unwrap();
+        let jbl = builder.build();
+        assert_eq!(jbl.to_string(), "{\"a\":\"x\",\"b\":1,\"c\":[1,2]}");
+    }
+
+    #[test]
+    fn test_merge_jbl() {
+        let mut jbl = JBL::new_object().unwrap();
+        jbl.set_prop("a", 1).unwrap();
+        jbl.set_prop("b", "x\"y").unwrap();
+        let other: JBL = "{\"a\":null,\"c\":3}".parse().unwrap();
+        jbl.merge_jbl(&other).unwrap();
+        assert_eq!(jbl.to_string(), "{\"b\":\"x\\\"y\",\"c\":3}");
+    }
+
+    #[test]
+    fn test_patch_jbl() {
+        let mut jbl: JBL = "{\"a\":1}".parse().unwrap();
+        let patch: JBL = "[{\"op\":\"add\",\"path\":\"/b\",\"value\":2}]"
+            .parse()
+            .unwrap();
+        jbl.patch_jbl(&patch).unwrap();
+        assert_eq!(jbl.to_string(), "{\"a\":1,\"b\":2}");
+    }
+
+    #[test]
+    fn test_get() {
+        let jbl: JBL = "{\"a\":{\"b\":[1,2]},\"c/d\":3}".parse().unwrap();
+        let a = jbl.get("a").unwrap();
+        let b = a.get("b").unwrap();
+        assert_eq!(b.count(), 2);
+        let c = jbl.get("c/d").unwrap();
+        assert_eq!(c.as_i64(), 3);
+        assert!(jbl.get("x").is_err());
+    }
+
+    #[test]
+    fn test_get_checked_int() {
+        let jbl: JBL = "{\"port\":8080,\"neg\":-1,\"big\":4294967296}"
+            .parse()
+            .unwrap();
+        assert_eq!(jbl.get_u16("port").unwrap(), 8080);
+        assert_eq!(jbl.get_u32("port").unwrap(), 8080);
+        assert_eq!(jbl.get_i32("neg").unwrap(), -1);
+        assert!(matches!(jbl.get_u64("neg"), Err(EjdbError::OutOfRange(-1))));
+        assert!(matches!(jbl.get_u32("big"), Err(EjdbError::OutOfRange(_))));
+        assert!(matches!(jbl.get_i32("big"), Err(EjdbError::OutOfRange(_))));
+        assert_eq!(jbl.get_u64("big").unwrap(), 4294967296);
+    }
+
+    #[test]
+    fn test_at() {
+        let arr: JBL = "[1,\"a\",[true]]".parse().unwrap();
+        assert_eq!(arr.len(), 3);
+        assert!(!arr.is_empty());
+        assert_eq!(arr.at(0).unwrap().as_i64(), 1);
+        assert_eq!(arr.at(1).unwrap().as_str(), "a");
+        assert_eq!(arr.at(2).unwrap().len(), 1);
+        assert!(arr.at(3).is_err());
+        let obj: JBL = "{\"0\":1}".parse().unwrap();
+        let res = obj.at(0);
+        assert!(matches!(res, Err(EjdbError::UnexpectedType { .. })));
+        assert!(JBL::new_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_print_truncated() {
+        let json =
+            "{\"a\":{\"b\":{\"c\":1}},\"d\":[1,[2]],\"e\":\"x\\\"y\",\"f\":{},\"g\\n\":null}";
+        let jbl: JBL = json.parse().unwrap();
+        let print = |depth| {
+            let mut buf = Vec::new();
+            jbl.print_truncated(&mut buf, None, depth).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        assert_eq!(print(0), "{...}");
+        assert_eq!(
+            print(2),
+            "{\"a\":{\"b\":{...}},\"d\":[1,[2]],\"e\":\"x\\\"y\",\"f\":{},\"g\\n\":null}"
+        );
+        assert_eq!(print(10), jbl.to_string());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_to_json_value() {
+        use serde_json::{json, Value};
+        let value = json!({
+            "a": {"b": [1, -2, 2.5, "x", null, true]},
+            "big": i64::MAX,
+            "c": [],
+            "d": {}
+        });
+        let jbl: JBL = value.to_string().parse().unwrap();
+        let res = jbl.to_json_value().unwrap();
+        assert_eq!(res, value);
+        assert_eq!(res["big"].as_i64(), Some(i64::MAX));
+        assert!(res["a"]["b"][2].is_f64());
+        assert_eq!(Value::try_from(&jbl).unwrap(), value);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        assert!(JBL::new_object().unwrap().is_empty());
+        let obj: JBL = "{\"a\":1}".parse().unwrap();
+        assert!(!obj.is_empty());
+        let arr: JBL = "[[]]".parse().unwrap();
+        assert!(!arr.is_empty());
+        assert!(arr.at(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut jbl: JBL = "{\"b\":1,\"a\":2,\"c\":3}".parse().unwrap();
+        assert_eq!(jbl.keys().unwrap(), vec!["b", "a", "c"]);
+        jbl.merge("{\"0\":4}").unwrap();
+        assert_eq!(jbl.keys().unwrap(), vec!["b", "a", "c", "0"]);
+        let arr: JBL = "[1,2]".parse().unwrap();
+        assert!(arr.keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_non_finite() {
+        let mut jbl = JBL::new_object().unwrap();
+        for v in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY].iter() {
+            let res = jbl.set_prop("a", *v);
+            assert!(matches!(res, Err(EjdbError::InvalidValue(_))));
+        }
+        assert_eq!(jbl.to_string(), "{}");
+    }
+
+    #[test]
+    fn test_get_at() {
+        let jbl: JBL = "{\"a\":{\"b\":[1,\"x\",2.5,true]}}".parse().unwrap();
+        assert_eq!(jbl.get_i64_at("/a/b/0").unwrap(), 1);
+        assert_eq!(jbl.get_f64_at("/a/b/0").unwrap(), 1.0);
+        assert_eq!(jbl.get_f64_at("/a/b/2").unwrap(), 2.5);
+        assert_eq!(jbl.get_i64_at("/a/b/2").unwrap(), 2);
+        assert_eq!(jbl.get_str_at("/a/b/1").unwrap(), "x");
+        assert!(jbl.get_bool_at("/a/b/3").unwrap());
+        let res = jbl.get_i64_at("/a/b/1");
+        assert!(matches!(res, Err(EjdbError::UnexpectedType { .. })));
+        let res = jbl.get_str_at("/a");
+        assert!(matches!(res, Err(EjdbError::UnexpectedType { .. })));
+        assert!(jbl.get_i64_at("/a/c").unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_type_at() {
+        let jbl: JBL = "{\"a\":{\"b\":[1,\"x\",null]}}".parse().unwrap();
+        assert_eq!(jbl.type_at("/a").unwrap(), JsonType::Object);
+        assert_eq!(jbl.type_at("/a/b").unwrap(), JsonType::Array);
+        assert_eq!(jbl.type_at("/a/b/0").unwrap(), JsonType::Integer);
+        assert_eq!(jbl.type_at("/a/b/1").unwrap(), JsonType::String);
+        assert_eq!(jbl.type_at("/a/b/2").unwrap(), JsonType::Null);
+        assert_eq!(jbl.type_at("/a/c").unwrap(), JsonType::None);
+        assert_eq!(jbl.type_at("/a/b/5").unwrap(), JsonType::None);
+        assert_eq!(jbl.type_at("/a/b/x").unwrap(), JsonType::None);
+        assert_eq!(jbl.type_at("/a/b/0/c").unwrap(), JsonType::None);
+        assert_eq!(jbl.type_at("").unwrap(), JsonType::Object);
+        assert!(jbl.type_at("a").is_err());
+        assert!(jbl.type_at("/a~2").is_err());
+    }
+
+    #[test]
+    fn test_get_at_escaped() {
+        let jbl: JBL = "{\"a/b\":{\"~\":[0,{\"\":7}]},\"a\":1}".parse().unwrap();
+        assert_eq!(jbl.get_i64_at("/a~1b/~0/1/").unwrap(), 7);
+        assert_eq!(jbl.get_i64_at("/a").unwrap(), 1);
+        assert!(jbl.get_i64_at("/a~1").unwrap_err().is_not_found());
+        // holder is reused by later lookups and elements of `for_each`
+        for _ in 0..3 {
+            assert_eq!(jbl.type_at("/a~1b/~0").unwrap(), JsonType::Array);
+        }
+        let mut found = Vec::new();
+        jbl.for_each(|_, v| {
+            found.push(v.type_at("/~0/0")?);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(found, vec![JsonType::Integer, JsonType::None]);
+    }
+
+    #[test]
+    fn test_find_opt() {
+        let jbl: JBL = "{\"address\":{\"zip\":\"100\"}}".parse().unwrap();
+        let zip = jbl.find_opt("/address/zip").unwrap().unwrap();
+        assert_eq!(zip.as_str(), "100");
+        assert!(jbl.find_opt("/address/city").unwrap().is_none());
+        assert!(jbl.find_opt("/phone/0").unwrap().is_none());
+        assert!(jbl.find_opt("address").is_err());
+    }
+
+    #[test]
+    fn test_value() {
+        let jbl: JBL = "{\"a\":1,\"b\":\"x\",\"c\":[true,null,1.5],\"d\":{}}"
+            .parse()
+            .unwrap();
+        assert!(matches!(jbl.value(), Ok(JBLValueRef::Object(_))));
+        let mut items = Vec::new();
+        jbl.visit_values(|key, v| {
+            let v = match v {
+                JBLValueRef::Int(v) => format!("{}", v),
+                JBLValueRef::Str(s) => s.to_owned(),
+                JBLValueRef::Array(arr) => {
+                    let mut types = Vec::new();
+                    arr.visit_values(|k, v| {
+                        assert!(k.is_none());
+                        types.push(format!("{:?}", v));
+                        Ok(())
+                    })?;
+                    types.join(",")
+                }
+                JBLValueRef::Object(obj) => obj.to_string(),
+                v => panic!("unexpected {:?}", v),
+            };
+            items.push((key.unwrap().to_owned(), v));
+            Ok(())
+        })
+        .unwrap();
+        let expected = vec![
+            ("a", "1"),
+            ("b", "x"),
+            ("c", "Bool(true),Null,Float(1.5)"),
+            ("d", "{}"),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        assert_eq!(items, expected);
+
+        let mut jbl = JBL::new_object().unwrap();
+        jbl.set_str(Some("a"), &XString::from(&b"\xff"[..]))
+            .unwrap();
+        let a = jbl.find("/a").unwrap();
+        assert!(matches!(a.value(), Err(EjdbError::Utf8Error(_))));
+        assert!(jbl.visit_values(|_, _| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_try_as_str() {
+        let jbl: JBL = "{\"a\":\"abc\"}".parse().unwrap();
+        let a = jbl.get("a").unwrap();
+        assert_eq!(a.try_as_str().unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_write_json_into() {
+        let jbl: JBL = "{\"a\":1}".parse().unwrap();
+        let mut buf = XString::new();
+        buf.push("garbage");
+        jbl.write_json_into(&mut buf, None).unwrap();
+        assert_eq!(buf, "{\"a\":1}");
+        jbl.write_json_into(&mut buf, None).unwrap();
+        assert_eq!(buf, "{\"a\":1}");
+    }
+
+    #[test]
+    fn test_display() {
+        let jbl: JBL = "{\"a\":1}".parse().unwrap();
+        assert_eq!(format!("{}", jbl), "{\"a\":1}");
+        assert_eq!(format!("{:?}", jbl), "JBL{json={\"a\":1}}");
+    }
+
+    #[test]
+    fn test_to_pretty_string() {
+        let jbl: JBL = "{\"a\":1,\"b\":[2]}".parse().unwrap();
+        let pretty = jbl.to_pretty_string().unwrap();
+        assert!(pretty.contains('\n'));
+        assert!(pretty.contains("\n "));
+        let compact: String = pretty.split_whitespace().collect();
+        assert_eq!(compact, "{\"a\":1,\"b\":[2]}");
+    }
+
+    #[test]
+    fn test_object_from() {
+        let jbl = JBL::object_from(vec![("a", 1), ("b", 2), ("a", 3)]).unwrap();
+        assert_eq!(jbl.to_string(), "{\"b\":2,\"a\":3}");
+        let jbl = JBL::object_from(vec![("s", "x".into_value()), ("n", JBLValue::Null)]);
+        assert_eq!(jbl.unwrap().to_string(), "{\"s\":\"x\",\"n\":null}");
+        let jbl = JBL::object_from(Vec::<(&str, i64)>::new()).unwrap();
+        assert_eq!(jbl.to_string(), "{}");
+        let jbl = JBL::array_from(vec!["a", "b"]).unwrap();
+        assert_eq!(jbl.to_string(), "[\"a\",\"b\"]");
+    }
+
+    #[test]
+    fn test_cmp_value() {
+        let jbl: JBL = "[1,2,1.5,\"a\",\"b\",true,false,null,{},[]]"
+            .parse()
+            .unwrap();
+        let v: Vec<JBL> = (0..jbl.count()).map(|i| jbl.at(i).unwrap()).collect();
+        assert_eq!(v[0].cmp_value(&v[1]), Some(Ordering::Less));
+        assert_eq!(v[1].cmp_value(&v[2]), Some(Ordering::Greater));
+        assert_eq!(v[2].cmp_value(&v[0]), Some(Ordering::Greater));
+        assert_eq!(v[3].cmp_value(&v[4]), Some(Ordering::Less));
+        assert_eq!(v[5].cmp_value(&v[6]), Some(Ordering::Greater));
+        assert_eq!(v[7].cmp_value(&v[7]), Some(Ordering::Equal));
+        assert_eq!(v[0].cmp_value(&v[3]), None);
+        assert_eq!(v[5].cmp_value(&v[0]), None);
+        assert_eq!(v[8].cmp_value(&v[8]), None);
+        assert_eq!(v[9].cmp_value(&v[9]), None);
+    }
+
+    #[test]
+    fn test_as_json_cstring() {
+        let jbl: JBL = "{\"a\":\"x\\u0000y\"}".parse().unwrap();
+        let s = jbl.as_json_cstring(None).unwrap();
+        assert_eq!(s.to_str().unwrap(), jbl.to_string());
+        assert!(!s.as_bytes().contains(&0));
+    }
+
+    #[test]
+    fn test_as_json_compact() {
+        let jbl: JBL = "{\"a\": \"é中\"}".parse().unwrap();
+        let raw: String = jbl.as_json_compact().unwrap();
+        assert_eq!(raw, "{\"a\":\"é中\"}");
+        let escaped: String = jbl.as_json(None).unwrap();
+        // 2 + 3 bytes of UTF-8 vs 6 + 6 bytes of `\uXXXX`
+        assert_eq!(escaped.len(), raw.len() + 7);
+        assert!(escaped.is_ascii());
+        let pretty: String = jbl.as_json(Some(JsonPrintFlags::PRINT_PRETTY)).unwrap();
+        assert!(pretty.len() > raw.len());
+    }
 }
@@ -0,0 +1,274 @@
+use core::ptr;
+
+use crate::{
+    ffi::{self, c_char, c_int},
+    jbl::{IntoJBLValue, JBLType, JBLValue, JsonType, JBL},
+    pool::Pool,
+    utils::{self, check_rc},
+    xstr::StringPtr,
+    EjdbError, Result,
+};
+use ejdb2_sys as sys;
+
+/// builder of JSON object or array, which accumulates a tree of nodes in a
+/// memory pool and converts it into JBL at once by `build`; it is cheaper
+/// than `JBL::set_prop`, which rewrites binary JSON for each call
+pub struct JblNodeBuilder {
+    pool: Pool,
+    root: sys::JBL_NODE,
+    /// object or array where items are added to
+    current: sys::JBL_NODE,
+}
+
+impl JblNodeBuilder {
+    /// builder of JSON object
+    #[inline]
+    pub fn new_object() -> Result<Self> {
+        Self::from_json(b"{}\0")
+    }
+
+    /// builder of JSON array
+    #[inline]
+    pub fn new_array() -> Result<Self> {
+        Self::from_json(b"[]\0")
+    }
+
+    #[inline]
+    fn from_json(json: &[u8]) -> Result<Self> {
+        let pool = Pool::new()?;
+        let mut root = ptr::null_mut();
+        let rc = unsafe {
+            sys::jbn_from_json(json.as_ptr() as *const c_char, &mut root, pool.as_mut_ptr())
+        };
+        check_rc(rc)?;
+        Ok(Self {
+            pool,
+            root,
+            current: root,
+        })
+    }
+
+    /// add property to current object
+    #[inline]
+    pub fn prop<'a, 'b>(
+        &mut self,
+        key: impl Into<StringPtr<'a>>,
+        val: impl IntoJBLValue<'b>,
+    ) -> Result<&mut Self> {
+        let key = key.into();
+        self.add(key.as_ptr(), val.into_value())?;
+        Ok(self)
+    }
+
+    /// add element to current array
+    #[inline]
+    pub fn push<'b>(&mut self, val: impl IntoJBLValue<'b>) -> Result<&mut Self> {
+        self.add(ptr::null(), val.into_value())?;
+        Ok(self)
+    }
+
+    /// start nested object as property of current object, until `end`
+    #[inline]
+    pub fn begin_object<'a>(&mut self, key: impl Into<StringPtr<'a>>) -> Result<&mut Self> {
+        let key = key.into();
+        self.current = self.add_container(key.as_ptr(), false)?;
+        Ok(self)
+    }
+
+    /// start nested array as property of current object, until `end`
+    #[inline]
+    pub fn begin_array<'a>(&mut self, key: impl Into<StringPtr<'a>>) -> Result<&mut Self> {
+        let key = key.into();
+        self.current = self.add_container(key.as_ptr(), true)?;
+        Ok(self)
+    }
+
+    /// start nested object as element of current array, until `end`
+    #[inline]
+    pub fn push_object(&mut self) -> Result<&mut Self> {
+        self.current = self.add_container(ptr::null(), false)?;
+        Ok(self)
+    }
+
+    /// start nested array as element of current array, until `end`
+    #[inline]
+    pub fn push_array(&mut self) -> Result<&mut Self> {
+        self.current = self.add_container(ptr::null(), true)?;
+        Ok(self)
+    }
+
+    /// finish nested object or array and continue with its parent;
+    /// no-op for root
+    #[inline]
+    pub fn end(&mut self) -> &mut Self {
+        let parent = unsafe { (*self.current).parent };
+        if !parent.is_null() {
+            self.current = parent;
+        }
+        self
+    }
+
+    /// convert node tree into JBL, nested objects or arrays
+    /// which are not ended are included as well
+    #[inline]
+    pub fn build(self) -> Result<JBL> {
+        let jbl = if unsafe { (*self.root).type_ } == JBLType::JBV_ARRAY {
+            JBL::new_array()?
+        } else {
+            JBL::new_object()?
+        };
+        let rc = unsafe { sys::jbl_fill_from_node(jbl.raw_ptr(), self.root) };
+        check_rc(rc)?;
+        Ok(jbl)
+    }
+
+    #[inline]
+    fn add_container(&mut self, key: *const c_char, array: bool) -> Result<sys::JBL_NODE> {
+        let pool = self.pool.as_mut_ptr();
+        let mut node = ptr::null_mut();
+        let rc = unsafe {
+            if array {
+                sys::jbn_add_item_arr(self.current, key, &mut node, pool)
+            } else {
+                sys::jbn_add_item_obj(self.current, key, &mut node, pool)
+            }
+        };
+        check_rc(rc)?;
+        Ok(node)
+    }
+
+    fn add(&mut self, key: *const c_char, val: JBLValue<'_>) -> Result<()> {
+        let pool = self.pool.as_mut_ptr();
+        let parent = self.current;
+        let mut node = ptr::null_mut();
+        let rc = unsafe {
+            match val {
+                JBLValue::Null => sys::jbn_add_item_null(parent, key, pool),
+                JBLValue::EmptyArray => sys::jbn_add_item_arr(parent, key, &mut node, pool),
+                JBLValue::EmptyObject => sys::jbn_add_item_obj(parent, key, &mut node, pool),
+                JBLValue::Boolean(v) => sys::jbn_add_item_bool(parent, key, v, &mut node, pool),
+                JBLValue::Float(v) => {
+                    let v = utils::check_finite(v)?;
+                    sys::jbn_add_item_f64(parent, key, v, &mut node, pool)
+                }
+                JBLValue::Integer(v) => sys::jbn_add_item_i64(parent, key, v, &mut node, pool),
+                JBLValue::String(v) => {
+                    let s = v.as_ptr();
+                    let len = ffi::strlen(s) as c_int;
+                    sys::jbn_add_item_str(parent, key, s, len, &mut node, pool)
+                }
+                JBLValue::Nested(v) => return self.add_nested(key, &v),
+                #[cfg(any(feature = "std", feature = "alloc"))]
+                JBLValue::Array(items) => {
+                    let arr = self.add_container(key, true)?;
+                    let prev = core::mem::replace(&mut self.current, arr);
+                    let res = items
+                        .into_iter()
+                        .try_for_each(|item| self.add(ptr::null(), item));
+                    self.current = prev;
+                    return res;
+                }
+            }
+        };
+        check_rc(rc)
+    }
+
+    fn add_nested(&mut self, key: *const c_char, jbl: &JBL) -> Result<()> {
+        let array = match jbl.value_type() {
+            JsonType::Object => false,
+            JsonType::Array => true,
+            actual => {
+                return Err(EjdbError::UnexpectedType {
+                    expected: JsonType::Object,
+                    actual,
+                })
+            }
+        };
+        let container = self.add_container(key, array)?;
+        let mut src = ptr::null_mut();
+        let rc = unsafe { sys::jbl_to_node(jbl.raw_ptr(), &mut src, true, self.pool.as_mut_ptr()) };
+        check_rc(rc)?;
+        // move children with their keys into container
+        let mut child = unsafe { (*src).child };
+        while !child.is_null() {
+            let next = unsafe { (*child).next };
+            unsafe { sys::jbn_add_item(container, child) };
+            child = next;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_node_builder() {
+        let nested: JBL = "{\"x\":[1,{\"y\":2}]}".parse().unwrap();
+        let mut builder = JblNodeBuilder::new_object().unwrap();
+        builder
+            .prop("a", 1)
+            .and_then(|b| b.prop("b", "s"))
+            .and_then(|b| b.begin_object("c"))
+            .and_then(|b| b.prop("d", true))
+            .unwrap();
+        builder.end();
+        builder
+            .begin_array("e")
+            .and_then(|b| b.push(1))
+            .and_then(|b| b.push(JBLValue::Null))
+            .and_then(|b| b.push_object())
+            .and_then(|b| b.prop("f", vec!["g"]))
+            .unwrap();
+        builder.end().end();
+        builder.prop("h", nested).unwrap();
+        let jbl = builder.build().unwrap();
+        assert_eq!(
+            jbl.to_string(),
+            "{\"a\":1,\"b\":\"s\",\"c\":{\"d\":true},\"e\":[1,null,{\"f\":[\"g\"]}],\"h\":{\"x\":[1,{\"y\":2}]}}"
+        );
+    }
+
+    #[test]
+    fn test_node_builder_array() {
+        let mut builder = JblNodeBuilder::new_array().unwrap();
+        builder.push("a").and_then(|b| b.push(2)).unwrap();
+        builder.push_array().and_then(|b| b.push(3)).unwrap();
+        let jbl = builder.build().unwrap();
+        assert_eq!(jbl.value_type(), JsonType::Array);
+        assert_eq!(jbl.at(0).unwrap().as_str(), "a");
+        assert_eq!(jbl.at(1).unwrap().as_i64(), 2);
+        assert_eq!(jbl.at(2).unwrap().to_string(), "[3]");
+    }
+
+    /// compare with `set_prop`, run by `cargo test -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_node_builder() {
+        use std::time::Instant;
+        const N: usize = 10000;
+        let keys: Vec<String> = (0..N).map(|i| format!("k{}", i)).collect();
+
+        let start = Instant::now();
+        let mut jbl = JBL::new_object().unwrap();
+        for (i, k) in keys.iter().enumerate() {
+            jbl.set_prop(k.as_str(), i).unwrap();
+        }
+        let set_prop = start.elapsed();
+
+        let start = Instant::now();
+        let mut builder = JblNodeBuilder::new_object().unwrap();
+        for (i, k) in keys.iter().enumerate() {
+            builder.prop(k.as_str(), i).unwrap();
+        }
+        let jbl2 = builder.build().unwrap();
+        let node_builder = start.elapsed();
+
+        assert_eq!(jbl.count(), jbl2.count());
+        println!(
+            "{} props: set_prop {:?}, node builder {:?}",
+            N, set_prop, node_builder
+        );
+    }
+}
@@ -1,11 +1,291 @@
 use core::ptr;
 
-use crate::{ffi::c_char, jbl::JBL, utils::check_rc, xstr::StringPtr, EjdbError, Result, XString};
+use crate::{
+    ffi::c_char,
+    jbl::{JsonType, JBL},
+    utils::{self, check_rc},
+    xstr::StringPtr,
+    EjdbError, RegexFlags, Result, XString,
+};
 use ejdb2_sys as sys;
 
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{string::String, vec::Vec};
+#[cfg(any(feature = "std", feature = "alloc"))]
+use core::cell::RefCell;
+
 const JQL_KEEP_QUERY_ON_PARSE_ERROR: u8 = 0x1;
 const JQL_SILENT_ON_PARSE_ERROR: u8 = 0x2;
 
+/// max length of collection name
+const COLLECTION_NAME_MAX_LEN: usize = 255;
+
+/// escape string to be placed within double quotes of a JQL string literal;
+/// `"` and `\` are escaped with `\`, `\n`, `\r`, `\t`, `\b`, `\f` by
+/// their short forms, and other control chars as `\u00XX`
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub fn escape_literal(val: &str) -> String {
+    use core::fmt::Write;
+    let mut res = String::with_capacity(val.len());
+    for c in val.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\u{8}' => res.push_str("\\b"),
+            '\u{c}' => res.push_str("\\f"),
+            c if c.is_control() => {
+                let _ = write!(res, "\\u{:04x}", c as u32);
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// JQL path with each key of `/` separated path quoted
+#[cfg(any(feature = "std", feature = "alloc"))]
+fn order_path(path: &str) -> String {
+    let mut res = String::with_capacity(path.len() + 4);
+    for key in path.split('/').filter(|k| !k.is_empty()) {
+        res.push_str("/\"");
+        res.push_str(&escape_literal(key));
+        res.push('"');
+    }
+    res
+}
+
+/// check if collection name can be placed in query anchor `@name/...`;
+/// only ASCII letters, digits, `_`, `-` and `.` are allowed, with at most
+/// 255 chars. There is no escaping for anchor, use
+/// `Database::query_with_collection` for any other names
+#[inline]
+pub fn is_valid_collection(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= COLLECTION_NAME_MAX_LEN
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-' || c == b'.')
+}
+
+/// validate regular expression and rewrite it with given flags
+#[cfg(any(feature = "std", feature = "alloc"))]
+fn prepare_regex(expr: &str, flags: RegexFlags) -> Result<String> {
+    let icase = flags.contains(RegexFlags::CASE_INSENSITIVE);
+    let mut res = String::with_capacity(expr.len());
+    let mut chars = expr.chars().peekable();
+    let mut depth = 0_usize;
+    // whether there is an item for repetition
+    let mut repeatable = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let e = chars
+                    .next()
+                    .ok_or(EjdbError::InvalidRegex("trailing backslash"))?;
+                res.push(c);
+                res.push(e);
+                repeatable = true;
+            }
+            '[' => {
+                res.push(c);
+                if chars.peek() == Some(&'^') {
+                    res.push('^');
+                    chars.next();
+                }
+                let mut extra = String::new();
+                let mut prev = None;
+                let mut empty = true;
+                loop {
+                    let c = chars
+                        .next()
+                        .ok_or(EjdbError::InvalidRegex("unclosed character class"))?;
+                    match c {
+                        ']' if empty => {
+                            return Err(EjdbError::InvalidRegex("empty character class"))
+                        }
+                        ']' => break,
+                        '\\' => {
+                            let e = chars
+                                .next()
+                                .ok_or(EjdbError::InvalidRegex("trailing backslash"))?;
+                            res.push(c);
+                            res.push(e);
+                            prev = None;
+                        }
+                        '-' if prev.is_some() && chars.peek().map_or(false, |v| *v != ']') => {
+                            let lo: char = prev.take().unwrap();
+                            let hi = chars.next().unwrap();
+                            if lo > hi {
+                                return Err(EjdbError::InvalidRegex("invalid character range"));
+                            }
+                            res.push(c);
+                            res.push(hi);
+                            let same_case = (lo.is_ascii_lowercase() && hi.is_ascii_lowercase())
+                                || (lo.is_ascii_uppercase() && hi.is_ascii_uppercase());
+                            if icase && same_case {
+                                // replace other case of `lo` by the whole range
+                                extra.pop();
+                                extra.push(swap_ascii_case(lo));
+                                extra.push('-');
+                                extra.push(swap_ascii_case(hi));
+                            }
+                        }
+                        c => {
+                            res.push(c);
+                            if icase && c.is_ascii_alphabetic() {
+                                extra.push(swap_ascii_case(c));
+                            }
+                            prev = Some(c);
+                        }
+                    }
+                    empty = false;
+                }
+                res.push_str(&extra);
+                res.push(']');
+                repeatable = true;
+            }
+            '(' => {
+                depth += 1;
+                res.push(c);
+                repeatable = false;
+            }
+            ')' => {
+                if depth == 0 {
+                    return Err(EjdbError::InvalidRegex("unmatched ')'"));
+                }
+                depth -= 1;
+                res.push(c);
+                repeatable = true;
+            }
+            '|' | '^' | '$' => {
+                res.push(c);
+                repeatable = false;
+            }
+            '*' | '+' | '?' | '{' if !repeatable => {
+                return Err(EjdbError::InvalidRegex("nothing to repeat"));
+            }
+            '{' => {
+                res.push(c);
+                let mut min = None;
+                let mut num: Option<u32> = None;
+                loop {
+                    let c = chars
+                        .next()
+                        .ok_or(EjdbError::InvalidRegex("unclosed repetition"))?;
+                    res.push(c);
+                    match c {
+                        '0'..='9' => {
+                            let d = c as u32 - '0' as u32;
+                            num = num
+                                .unwrap_or(0)
+                                .checked_mul(10)
+                                .and_then(|v| v.checked_add(d));
+                            if num.is_none() {
+                                return Err(EjdbError::InvalidRegex("invalid repetition"));
+                            }
+                        }
+                        ',' if min.is_none() && num.is_some() => {
+                            min = num.take();
+                        }
+                        '}' => break,
+                        _ => return Err(EjdbError::InvalidRegex("invalid repetition")),
+                    }
+                }
+                match (min, num) {
+                    (None, None) => return Err(EjdbError::InvalidRegex("invalid repetition")),
+                    (Some(lo), Some(hi)) if lo > hi => {
+                        return Err(EjdbError::InvalidRegex("invalid repetition"))
+                    }
+                    _ => {}
+                }
+            }
+            c if icase && c.is_ascii_alphabetic() => {
+                res.push('[');
+                res.push(c);
+                res.push(swap_ascii_case(c));
+                res.push(']');
+                repeatable = true;
+            }
+            c => {
+                res.push(c);
+                repeatable = true;
+            }
+        }
+    }
+    if depth > 0 {
+        return Err(EjdbError::InvalidRegex("unclosed group"));
+    }
+    Ok(res)
+}
+
+#[inline]
+fn swap_ascii_case(c: char) -> char {
+    if c.is_ascii_lowercase() {
+        c.to_ascii_uppercase()
+    } else {
+        c.to_ascii_lowercase()
+    }
+}
+
+/// placeholder of query, positional ones are indexed from 0
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Placeholder {
+    Named(String),
+    Positional(i32),
+}
+
+/// placeholders in query text, in order of appearance;
+/// string literals and JSON objects are skipped
+#[cfg(any(feature = "std", feature = "alloc"))]
+fn parse_placeholders(query: &str) -> Vec<Placeholder> {
+    let mut res = Vec::new();
+    let mut index = 0;
+    let mut depth = 0_usize;
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 => {
+                if chars.peek() == Some(&'?') {
+                    chars.next();
+                    res.push(Placeholder::Positional(index));
+                    index += 1;
+                    continue;
+                }
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                if !name.is_empty() {
+                    res.push(Placeholder::Named(name));
+                }
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
 #[inline(always)]
 fn jql_error(handle: sys::JQL) -> XString {
     let msg = unsafe { sys::jql_error(handle) };
@@ -15,6 +295,11 @@ fn jql_error(handle: sys::JQL) -> XString {
 #[derive(Debug)]
 pub struct JQL {
     handle: sys::JQL,
+    /// query text, kept to parse again for another collection
+    query: XString,
+    /// placeholders bound since created or reset
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    bound: RefCell<Vec<Placeholder>>,
 }
 
 impl JQL {
@@ -35,6 +320,7 @@ impl JQL {
     }
     #[inline]
     fn create_jql<'a, 'b>(query: StringPtr<'a>, coll: Option<StringPtr<'b>>) -> Result<Self> {
+        let query = query.to_owned();
         let mut handle = ptr::null_mut();
         let mode = JQL_KEEP_QUERY_ON_PARSE_ERROR | JQL_SILENT_ON_PARSE_ERROR;
         let coll_ptr = match coll {
@@ -52,7 +338,41 @@ impl JQL {
             }
             return Err(e);
         }
-        Ok(Self { handle })
+        Ok(Self {
+            handle,
+            query,
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            bound: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// parse the same query for another collection, which takes priority
+    /// over collection anchor of the query;
+    /// EJDB2 can neither rebind collection nor clone a parsed query,
+    /// so query text is parsed again, placeholder values are not copied
+    #[inline]
+    pub fn with_collection<'a>(&self, collection: impl Into<StringPtr<'a>>) -> Result<Self> {
+        Self::create_jql(StringPtr::XStringRef(&self.query), Some(collection.into()))
+    }
+
+    /// parse the same query with ordering by `path` appended,
+    /// e.g. `| asc /"a"/"b"` for path `/a/b`; each key of path is quoted,
+    /// so user input can not change the query. EJDB2 can not modify a
+    /// parsed query, so query text is parsed again for the same collection,
+    /// placeholder values are not copied; fails with
+    /// `EjdbError::JQLParseError` if query already has an options section,
+    /// e.g. `| skip 1`, compose ordering there instead
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn with_order(&self, path: &str, ascending: bool) -> Result<Self> {
+        let mut query = String::from(self.source());
+        query.push_str(if ascending { " | asc " } else { " | desc " });
+        query.push_str(&order_path(path));
+        let coll = self.collection()?;
+        if coll.is_empty() {
+            Self::create(query)
+        } else {
+            Self::create_with_collection(query, coll)
+        }
     }
 
     #[inline(always)]
@@ -60,6 +380,14 @@ impl JQL {
         self.handle
     }
 
+    /// query text as given on create; EJDB2 can not print a parsed query,
+    /// so the text is not normalized, and a collection given on create
+    /// is not reflected
+    #[inline]
+    pub fn source(&self) -> &str {
+        self.query.as_str()
+    }
+
     /// collection name from query
     #[inline]
     pub fn collection(&self) -> Result<XString> {
@@ -68,6 +396,24 @@ impl JQL {
         Ok(res)
     }
 
+    /// true if query modifies data, i.e. has `apply`, `upsert` or `del`
+    #[inline(always)]
+    pub fn has_apply(&self) -> bool {
+        unsafe { sys::jql_has_apply(self.raw_ptr()) }
+    }
+
+    /// true if query has projection
+    #[inline(always)]
+    pub fn has_projection(&self) -> bool {
+        unsafe { sys::jql_has_projection(self.raw_ptr()) }
+    }
+
+    /// true if query is `count` aggregation
+    #[inline(always)]
+    pub fn is_aggregate_count(&self) -> bool {
+        unsafe { sys::jql_has_aggregate_count(self.raw_ptr()) }
+    }
+
     #[inline(always)]
     pub fn skip(&self) -> Result<i64> {
         let mut num = 0;
@@ -92,25 +438,28 @@ impl JQL {
     pub fn set_i64<'a>(&self, key: impl Into<KeyParam<'a>>, val: i64) -> Result<()> {
         let key: KeyParam<'_> = key.into();
         let rc = unsafe { sys::jql_set_i64(self.raw_ptr(), key.as_ptr(), key.as_index(), val) };
-        check_rc(rc)
+        self.check_bound(rc, &key)
     }
 
     #[inline]
     pub fn set_bool<'a>(&self, key: impl Into<KeyParam<'a>>, val: bool) -> Result<()> {
         let key: KeyParam<'_> = key.into();
         let rc = unsafe { sys::jql_set_bool(self.raw_ptr(), key.as_ptr(), key.as_index(), val) };
-        check_rc(rc)
+        self.check_bound(rc, &key)
     }
 
     #[inline]
     pub fn set_f64<'a>(&self, key: impl Into<KeyParam<'a>>, val: f64) -> Result<()> {
         let key: KeyParam<'_> = key.into();
+        let val = utils::check_finite(val)?;
         let rc = unsafe { sys::jql_set_f64(self.raw_ptr(), key.as_ptr(), key.as_index(), val) };
-        check_rc(rc)
+        self.check_bound(rc, &key)
     }
 
+    /// bind JSON object/array placeholder;
+    /// returns `EjdbError::InvalidJson` if JSON can not be parsed
     #[inline]
-    pub(crate) fn set_json<'a, 'b>(
+    pub fn set_json<'a, 'b>(
         &self,
         key: impl Into<KeyParam<'a>>,
         val: impl Into<StringPtr<'b>>,
@@ -120,7 +469,52 @@ impl JQL {
         let rc = unsafe {
             sys::jql_set_json_jbl(self.raw_ptr(), key.as_ptr(), key.as_index(), jbl.raw_ptr())
         };
-        check_rc(rc)
+        self.check_bound(rc, &key)
+    }
+
+    /// bind array of strings to placeholder of `in`/`ni` condition,
+    /// e.g. `:statuses` of `/[status in :statuses]`; EJDB2 has no binding
+    /// of single array elements, so the whole array is bound at once
+    #[inline]
+    pub fn set_str_in<'a>(&self, key: impl Into<KeyParam<'a>>, values: &[&str]) -> Result<()> {
+        let jbl = JBL::from_values(values)?;
+        self.set_json_jbl(key, &jbl)
+    }
+
+    /// bind JSON object/array placeholder, e.g. `:doc` of `| apply :doc`;
+    /// returns `EjdbError::UnexpectedType` for other JSON values
+    #[inline]
+    pub fn set_document<'a, 'b>(
+        &self,
+        key: impl Into<KeyParam<'a>>,
+        json: impl Into<StringPtr<'b>>,
+    ) -> Result<()> {
+        let doc = JBL::from_json(json)?;
+        self.set_document_jbl(key, &doc)
+    }
+
+    /// bind JSON object/array placeholder with a copy of `doc`
+    #[inline]
+    pub fn set_document_jbl<'a>(&self, key: impl Into<KeyParam<'a>>, doc: &JBL) -> Result<()> {
+        match doc.value_type() {
+            JsonType::Object | JsonType::Array => self.set_json_jbl(key, doc),
+            actual => Err(EjdbError::UnexpectedType {
+                expected: JsonType::Object,
+                actual,
+            }),
+        }
+    }
+
+    /// bind placeholder with serde JSON value
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub fn set_value<'a>(
+        &self,
+        key: impl Into<KeyParam<'a>>,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        let json = serde_json::to_string(value).map_err(|e| EjdbError::Other(Box::new(e)))?;
+        self.set_json(key, json)
     }
 
     #[inline]
@@ -131,7 +525,7 @@ impl JQL {
     ) -> Result<()> {
         let key: KeyParam<'_> = key.into();
         let rc = unsafe { sys::jql_set_json(self.raw_ptr(), key.as_ptr(), key.as_index(), val) };
-        check_rc(rc)
+        self.check_bound(rc, &key)
     }
 
     #[inline]
@@ -144,14 +538,17 @@ impl JQL {
         let rc = unsafe {
             sys::jql_set_json_jbl(self.raw_ptr(), key.as_ptr(), key.as_index(), val.raw_ptr())
         };
-        check_rc(rc)
+        self.check_bound(rc, &key)
     }
 
+    /// bind JSON null, which matches properties with null value only;
+    /// docs without the property never match any condition, as EJDB2 only
+    /// evaluates existing nodes, see `Query::missing_ids` for absent ones
     #[inline]
     pub fn set_null<'a>(&self, key: impl Into<KeyParam<'a>>) -> Result<()> {
         let key: KeyParam<'_> = key.into();
         let rc = unsafe { sys::jql_set_null(self.raw_ptr(), key.as_ptr(), key.as_index()) };
-        check_rc(rc)
+        self.check_bound(rc, &key)
     }
 
     #[inline]
@@ -165,7 +562,27 @@ impl JQL {
         let rc = unsafe {
             sys::jql_set_regexp(self.raw_ptr(), key.as_ptr(), key.as_index(), expr.as_ptr())
         };
-        check_rc(rc)
+        self.check_bound(rc, &key)
+    }
+
+    /// bind regular expression placeholder with flags;
+    /// the expression is validated up front, returns `EjdbError::InvalidRegex`
+    /// if malformed.
+    ///
+    /// EJDB2 matches with lwre, a small engine without any flags:
+    /// there is no multiline mode and `^`/`$` anchor the whole value;
+    /// `CASE_INSENSITIVE` is emulated by rewriting ASCII letters
+    /// into character classes, e.g. `ab[c-d]` into `[aA][bB][c-dC-D]`
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn set_regex_with_flags<'a>(
+        &self,
+        key: impl Into<KeyParam<'a>>,
+        expr: &str,
+        flags: RegexFlags,
+    ) -> Result<()> {
+        let expr = prepare_regex(expr, flags)?;
+        self.set_regex(key, expr)
     }
 
     #[inline]
@@ -178,15 +595,66 @@ impl JQL {
         let val = val.into();
         let rc =
             unsafe { sys::jql_set_str(self.raw_ptr(), key.as_ptr(), key.as_index(), val.as_ptr()) };
-
-        check_rc(rc)
+        self.check_bound(rc, &key)
     }
 
     #[inline(always)]
     pub fn reset(&self, reset_match_cache: bool, reset_placeholders: bool) -> &Self {
         unsafe { sys::jql_reset(self.raw_ptr(), reset_match_cache, reset_placeholders) };
+        #[cfg(any(feature = "std", feature = "alloc"))]
+        {
+            if reset_placeholders {
+                self.bound.borrow_mut().clear();
+            }
+        }
         self
     }
+
+    /// check rc and record placeholder as bound
+    #[cfg_attr(not(any(feature = "std", feature = "alloc")), allow(unused_variables))]
+    #[inline]
+    fn check_bound(&self, rc: u64, key: &KeyParam<'_>) -> Result<()> {
+        check_rc(rc)?;
+        #[cfg(any(feature = "std", feature = "alloc"))]
+        {
+            let placeholder = match key.name {
+                Some(ref name) => {
+                    Placeholder::Named(XString::from_str_ptr(name.as_ptr()).as_str().into())
+                }
+                None => Placeholder::Positional(key.index),
+            };
+            let mut bound = self.bound.borrow_mut();
+            if !bound.contains(&placeholder) {
+                bound.push(placeholder);
+            }
+        }
+        Ok(())
+    }
+
+    /// check all placeholders in query text are bound, fails with
+    /// `EjdbError::UnboundPlaceholder` naming the first unbound one,
+    /// e.g. `:name` or `:?` with its index
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn ensure_bound(&self) -> Result<()> {
+        use core::fmt::Write;
+        let bound = self.bound.borrow();
+        for placeholder in parse_placeholders(self.source()) {
+            if bound.contains(&placeholder) {
+                continue;
+            }
+            let mut name = XString::new();
+            match placeholder {
+                Placeholder::Named(v) => {
+                    name.push(":").push(v);
+                }
+                Placeholder::Positional(i) => {
+                    let _ = write!(name, ":? at index {}", i);
+                }
+            }
+            return Err(EjdbError::UnboundPlaceholder(name));
+        }
+        Ok(())
+    }
 }
 
 impl Drop for JQL {
@@ -298,6 +766,69 @@ mod test {
         assert_eq!(name, "abc");
     }
 
+    #[test]
+    fn test_jql_with_collection() {
+        let query = JQL::create("@c1/[a = :?] | limit 2").unwrap();
+        let other = query.with_collection("c2").unwrap();
+        assert_eq!(other.collection().unwrap(), "c2");
+        assert_eq!(other.limit().unwrap(), 2);
+        assert_eq!(query.collection().unwrap(), "c1");
+    }
+
+    #[test]
+    fn test_jql_source() {
+        let text = "@c1/[a = :?] | limit 2";
+        let query = JQL::create(text).unwrap();
+        assert_eq!(query.source(), text);
+        let other = query.with_collection("c2").unwrap();
+        assert_eq!(other.source(), text);
+    }
+
+    #[test]
+    fn test_parse_placeholders() {
+        let list = parse_placeholders(
+            "@c1/[a = :?] and /[b = \":x\"] and /[c in :list] | apply {\"d\":1} | apply :doc",
+        );
+        assert_eq!(
+            list,
+            vec![
+                Placeholder::Positional(0),
+                Placeholder::Named("list".into()),
+                Placeholder::Named("doc".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_f64_non_finite() {
+        let query = JQL::create("@c1/[a > :?]").unwrap();
+        let res = query.set_f64(0, f64::NAN);
+        assert!(matches!(res, Err(EjdbError::InvalidValue(_))));
+        let res = query.set_f64(0, f64::INFINITY);
+        assert!(matches!(res, Err(EjdbError::InvalidValue(_))));
+        query.set_f64(0, 1.5).unwrap();
+    }
+
+    #[test]
+    fn test_ensure_bound() {
+        let query = JQL::create("@c1/[a = :?] and /[b = :name] and /[c = :?]").unwrap();
+        let res = query.ensure_bound();
+        assert!(
+            matches!(res, Err(EjdbError::UnboundPlaceholder(ref n)) if n.as_str() == ":? at index 0")
+        );
+        query.set_i64(0, 1).unwrap();
+        query.set_str("name", "x").unwrap();
+        let res = query.ensure_bound();
+        assert!(
+            matches!(res, Err(EjdbError::UnboundPlaceholder(ref n)) if n.as_str() == ":? at index 1")
+        );
+        query.set_null(1).unwrap();
+        query.ensure_bound().unwrap();
+        query.reset(false, true);
+        let res = query.ensure_bound();
+        assert!(res.is_err());
+    }
+
     #[test]
     fn test_jql_limit_not_set() {
         let query = JQL::create("@abc/*").unwrap();
@@ -316,6 +847,51 @@ mod test {
         assert_eq!(skip, 3);
     }
 
+    #[test]
+    fn test_escape_literal() {
+        let val = "a\"b\\c\nd\u{1}";
+        assert_eq!(escape_literal(val), "a\\\"b\\\\c\\nd\\u0001");
+        let query = JQL::create(format!("@c1/[a = \"{}\"]", escape_literal(val))).unwrap();
+        assert!(!query.has_apply());
+    }
+
+    #[test]
+    fn test_with_order() {
+        assert_eq!(order_path("/a/b c"), "/\"a\"/\"b c\"");
+        assert_eq!(order_path("/x\"] | del"), "/\"x\\\"] | del\"");
+        let query = JQL::create("@c1/*").unwrap();
+        let query = query.with_order("/a\" | del", false).unwrap();
+        assert!(!query.has_apply());
+        assert_eq!(query.collection().unwrap(), "c1");
+        assert!(query.source().ends_with(" | desc /\"a\\\" | del\""));
+    }
+
+    #[test]
+    fn test_valid_collection() {
+        assert!(is_valid_collection("c1"));
+        assert!(is_valid_collection("my_coll-1.x"));
+        assert!(!is_valid_collection(""));
+        assert!(!is_valid_collection("c1/*|del"));
+        assert!(!is_valid_collection("a b"));
+    }
+
+    #[test]
+    fn test_jql_introspection() {
+        let query = JQL::create("@c1/*").unwrap();
+        assert!(!query.has_apply());
+        assert!(!query.has_projection());
+        assert!(!query.is_aggregate_count());
+
+        let query = JQL::create("@c1/* | apply {\"a\":1}").unwrap();
+        assert!(query.has_apply());
+        let query = JQL::create("@c1/* | del").unwrap();
+        assert!(query.has_apply());
+        let query = JQL::create("@c1/* | /a").unwrap();
+        assert!(query.has_projection());
+        let query = JQL::create("@c1/* | count").unwrap();
+        assert!(query.is_aggregate_count());
+    }
+
     #[test]
     fn test_jql_named_params() {
         let query = JQL::create("@c1/[name=:name and age=:age]").unwrap();
@@ -329,4 +905,47 @@ mod test {
         query.set_str(0, "john").unwrap();
         query.set_i64(1, 20).unwrap();
     }
+
+    #[test]
+    fn test_prepare_regex() {
+        let none = RegexFlags::empty();
+        let icase = RegexFlags::CASE_INSENSITIVE;
+        assert_eq!(prepare_regex("^a.b*$", none).unwrap(), "^a.b*$");
+        assert_eq!(prepare_regex("ab[c-d]", icase).unwrap(), "[aA][bB][c-dC-D]");
+        assert_eq!(prepare_regex("\\d{1,3}x", icase).unwrap(), "\\d{1,3}[xX]");
+        assert_eq!(prepare_regex("[^a1]", icase).unwrap(), "[^a1A]");
+        for expr in &[
+            "(a", "a)", "*a", "a\\", "[a", "[]", "a{3,1}", "a{x}", "[z-a]",
+        ] {
+            let res = prepare_regex(expr, none);
+            assert!(matches!(res, Err(EjdbError::InvalidRegex(_))), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_jql_regex_param() {
+        let query = JQL::create("@c1/[name re :name]").unwrap();
+        query
+            .set_regex_with_flags("name", "^lily", RegexFlags::CASE_INSENSITIVE)
+            .unwrap();
+        let res = query.set_regex_with_flags("name", "(lily", RegexFlags::empty());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_jql_json_param() {
+        let query = JQL::create("@c1/[c in :list]").unwrap();
+        query.set_json("list", "[1,2,3]").unwrap();
+        let res = query.set_json("list", "[1,2");
+        assert!(matches!(res, Err(EjdbError::InvalidJson(_))));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_jql_value_param() {
+        let query = JQL::create("@c1/[c in :list]").unwrap();
+        query
+            .set_value("list", &serde_json::json!([1, 2, 3]))
+            .unwrap();
+    }
 }
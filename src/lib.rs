@@ -16,13 +16,21 @@ pub mod error;
 pub mod exec;
 mod ffi;
 mod jbl;
+mod jbn;
 pub mod jql;
+mod pool;
 pub mod printer;
+#[cfg(feature = "alloc-stats")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod util;
 mod utils;
 mod xstr;
 
 pub use builder::EJDB2Builder;
-pub use database::Database;
+#[cfg(feature = "std")]
+pub use database::SharedDatabase;
+pub use database::{Database, ReadOnlyDatabase};
 pub use error::EjdbError;
 pub type Result<T> = core::result::Result<T, EjdbError>;
 
@@ -32,17 +40,55 @@ bitflags! {
         const IWKV_RDONLY                  = 0x2;
         /** Truncate storage file on open */
         const IWKV_TRUNC                   = 0x4;
+        /** Do not trim unused tail of storage file on close */
+        const IWKV_NO_TRIM_ON_CLOSE        = 0x8;
     }
 }
 
 bitflags! {
+    /// index mode, value type combined with optional uniqueness
+    pub struct IndexMode: u8 {
+        /** No duplicated values allowed */
+        const UNIQUE                       = 0x01;
+        /** Index values of string type */
+        const STR                          = 0x04;
+        /** Index values of 64 bit signed integer type */
+        const I64                          = 0x08;
+        /** Index values of floating point type */
+        const F64                          = 0x10;
+    }
+}
+
+bitflags! {
+    /// flags of regular expression placeholder
+    pub struct RegexFlags: u8 {
+        /** Case insensitive matching of ASCII letters */
+        const CASE_INSENSITIVE = 0x1;
+    }
+}
+
+bitflags! {
+    /// JSON output flags; without any flag output is compact raw UTF-8.
+    /// `None` passed to `as_json` or `print` means `PRINT_CODEPOINTS`
     pub struct JsonPrintFlags: u8 {
+        /// indent output with new lines
         const PRINT_PRETTY = 0x1;
+        /// escape non-ASCII chars as `\uXXXX`, output is pure ASCII
         const PRINT_CODEPOINTS =0x2;
     }
 }
 
-pub use ffi::ejdb_version;
+impl JsonPrintFlags {
+    /// compact output with raw UTF-8, the smallest output
+    pub const COMPACT_RAW: JsonPrintFlags = JsonPrintFlags::empty();
+}
+
+pub use ffi::{
+    assert_version_compatible, ejdb_version, set_log_handler, set_log_level, LogHandler, LogLevel,
+    Version, EJDB2_MIN_VERSION,
+};
+#[cfg(feature = "std")]
+pub use util::retry;
 pub use xstr::{StringPtr, XString};
 
 pub mod precludes {
@@ -51,10 +97,11 @@ pub mod precludes {
         database::Database,
         error::EjdbError,
         exec::{Query, VisitStep, Visitor},
-        jbl::{JBLType, JBLValue},
+        jbl::{JBLObjectBuilder, JBLRef, JBLType, JBLValue, JBLValueRef, JsonType},
+        jbn::JblNodeBuilder,
         jql::{KeyParam, JQL},
         printer::{AsJson, JsonPrinter},
-        DatabaseOpenMode, JsonPrintFlags, Result,
+        DatabaseOpenMode, IndexMode, JsonPrintFlags, RegexFlags, Result,
     };
 }
 
@@ -0,0 +1,34 @@
+use crate::{EjdbError, Result};
+use ejdb2_sys as sys;
+
+/// iwpool, memory pool for JBL_NODE
+pub(crate) struct Pool {
+    ptr: *mut sys::IWPOOL,
+}
+
+impl Pool {
+    #[inline]
+    pub fn new() -> Result<Self> {
+        let ptr = unsafe { sys::iwpool_create(0) };
+        if ptr.is_null() {
+            return Err(EjdbError::AllocError);
+        }
+        #[cfg(feature = "alloc-stats")]
+        crate::stats::POOLS.inc();
+        Ok(Self { ptr })
+    }
+
+    #[inline(always)]
+    pub fn as_mut_ptr(&self) -> *mut sys::IWPOOL {
+        self.ptr
+    }
+}
+
+impl Drop for Pool {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { sys::iwpool_destroy(self.ptr) };
+        #[cfg(feature = "alloc-stats")]
+        crate::stats::POOLS.dec();
+    }
+}
@@ -4,11 +4,30 @@ use crate::{
     utils::check_rc,
     JsonPrintFlags, Result,
 };
-use core::{cmp, mem, slice};
+use core::{cmp, fmt, mem, slice};
 use ejdb2_sys as sys;
 pub trait AsJson<T> {
-    /// to JSON string
+    /// to JSON string, non-ASCII chars are escaped if flag is `None`
     fn as_json(&self, flag: Option<JsonPrintFlags>) -> Result<T>;
+
+    /// to compact JSON string with raw UTF-8
+    #[inline]
+    fn as_json_compact(&self) -> Result<T> {
+        self.as_json(Some(JsonPrintFlags::COMPACT_RAW))
+    }
+}
+
+/// placeholder for Display if JSON can not be printed
+pub(crate) const INVALID_JSON: &str = "<invalid json>";
+
+/// print flags for Display, pretty if `{:#}`
+#[inline]
+pub(crate) fn fmt_flags(f: &fmt::Formatter<'_>) -> JsonPrintFlags {
+    if f.alternate() {
+        JsonPrintFlags::PRINT_PRETTY | JsonPrintFlags::PRINT_CODEPOINTS
+    } else {
+        JsonPrintFlags::PRINT_CODEPOINTS
+    }
 }
 
 pub trait JsonPrinter {
@@ -26,6 +45,17 @@ impl JsonPrinter for crate::XString {
     }
 }
 
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+impl JsonPrinter for alloc::vec::Vec<u8> {
+    #[inline]
+    fn print(&mut self, buf: &[u8], count: usize) -> Result<()> {
+        for _ in 0..count {
+            self.extend_from_slice(buf);
+        }
+        Ok(())
+    }
+}
+
 #[cfg(feature = "std")]
 impl<T: std::io::Write> JsonPrinter for T {
     #[inline]
@@ -73,6 +103,39 @@ pub(crate) fn jbl_print_json<T: JsonPrinter>(
     check_rc(rc)
 }
 
+/// print object key as JSON string followed by `:`;
+/// quotes, backslashes and control chars are escaped, other bytes are kept
+pub(crate) fn print_json_key<T: JsonPrinter>(target: &mut T, key: &[u8]) -> Result<()> {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    target.print(b"\"", 1)?;
+    let mut start = 0;
+    for (i, &b) in key.iter().enumerate() {
+        let mut hex = *b"\\u0000";
+        let esc: &[u8] = match b {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0..=0x1f => {
+                hex[4] = HEX[(b >> 4) as usize];
+                hex[5] = HEX[(b & 0xf) as usize];
+                &hex
+            }
+            _ => continue,
+        };
+        if start < i {
+            target.print(&key[start..i], 1)?;
+        }
+        target.print(esc, 1)?;
+        start = i + 1;
+    }
+    if start < key.len() {
+        target.print(&key[start..], 1)?;
+    }
+    target.print(b"\":", 1)
+}
+
 unsafe extern "C" fn print_json<T: JsonPrinter>(
     data: *const i8,
     size: i32,
@@ -0,0 +1,71 @@
+//! memory of `XString`, `JBL` and node pools is allocated by the C library
+//! with `malloc`/`realloc`/`free` of the linked C runtime; neither EJDB2 nor
+//! iowow has an allocator hook, so allocations can only be routed elsewhere
+//! by linking a replacement `malloc`. Live handles owned by this crate are
+//! counted to help spot leaks or growth.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// number of live handles owned by this crate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// live `XString` buffers
+    pub xstrings: usize,
+    /// live `JBL` documents
+    pub jbls: usize,
+    /// live memory pools of JSON nodes
+    pub pools: usize,
+}
+
+pub(crate) struct Counter(AtomicUsize);
+
+impl Counter {
+    const fn new() -> Self {
+        Self(AtomicUsize::new(0))
+    }
+
+    #[inline(always)]
+    pub(crate) fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub(crate) fn dec(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) static XSTRINGS: Counter = Counter::new();
+pub(crate) static JBLS: Counter = Counter::new();
+pub(crate) static POOLS: Counter = Counter::new();
+
+/// snapshot of live handles
+#[inline]
+pub fn alloc_stats() -> AllocStats {
+    AllocStats {
+        xstrings: XSTRINGS.get(),
+        jbls: JBLS.get(),
+        pools: POOLS.get(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{jbl::JBL, XString};
+
+    #[test]
+    fn test_alloc_stats() {
+        // other tests run in parallel, so exact counts are not checked
+        let _s = XString::new();
+        let _jbl = JBL::new_object().unwrap();
+        let stats = alloc_stats();
+        assert!(stats.xstrings >= 1);
+        assert!(stats.jbls >= 1);
+    }
+}
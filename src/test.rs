@@ -7,21 +7,16 @@ use std::{
 
 use rand::Rng;
 
-use crate::{Database, DatabaseOpenMode, EJDB2Builder, Result};
+use crate::{Database, EJDB2Builder, Result};
+/// database deleted on drop
 pub(crate) struct TestDb {
-    file: String,
     db: Database,
 }
 
 impl TestDb {
     pub fn new() -> Self {
-        let num = next_u64(100000);
-        let file = format!("{}-{}", get_tmp_path(), num);
-        eprintln!("db file: {}", &file);
-        let file_ref: &str = file.as_ref();
-        let opts = EJDB2Builder::new(file_ref).oflags(DatabaseOpenMode::IWKV_TRUNC);
-        let db = opts.build().unwrap();
-        Self { file, db }
+        let db = EJDB2Builder::temporary().build().unwrap();
+        Self { db }
     }
 
     pub fn new_with_seed() -> Result<Self> {
@@ -55,21 +50,26 @@ impl DerefMut for TestDb {
     }
 }
 
-impl Drop for TestDb {
-    fn drop(&mut self) {
-        let res = retry(|| fs::remove_file(&self.file).map_err(|e| e.into()), 10);
-        if let Err(e) = res {
-            eprintln!("{}", e)
-        }
+/// temp file path for test
+pub(crate) fn tmp_file(name: &str) -> String {
+    let num = next_u64(100000);
+    format!("{}-{}-{}", get_tmp_path(), name, num)
+}
 
-        let wal_file = format!("{}-wal", &self.file);
-        let res = retry(
-            || fs::remove_file(wal_file.as_str()).map_err(|e| e.into()),
-            5,
-        );
-        if let Err(e) = res {
-            eprintln!("{}", e)
-        }
+/// remove database file and its WAL file
+pub(crate) fn remove_db_files(file: &str) {
+    let res = retry(|| fs::remove_file(file).map_err(|e| e.into()), 10);
+    if let Err(e) = res {
+        eprintln!("{}", e)
+    }
+
+    let wal_file = format!("{}-wal", file);
+    let res = retry(
+        || fs::remove_file(wal_file.as_str()).map_err(|e| e.into()),
+        5,
+    );
+    if let Err(e) = res {
+        eprintln!("{}", e)
     }
 }
 
@@ -0,0 +1,148 @@
+//! helpers for applications
+
+use crate::{utils, EjdbError, Result};
+use ejdb2_sys as sys;
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+/// true if error is transient and the operation can be retried:
+/// - `EjdbError::Locked`, database file opened by another handle
+/// - `IW_ERROR_AGAIN` of iowow, storage engine asking to try again
+/// - `IW_ERROR_ERRNO` with `EAGAIN`/`EACCES`, file locked by another process,
+///   see `utils::is_locked`
+/// - IO errors of kind `Interrupted`, `WouldBlock` or `TimedOut`
+///
+/// everything else is permanent, e.g. invalid arguments, auth or
+/// corruption errors, as retrying can not change the outcome
+pub fn is_retryable(e: &EjdbError) -> bool {
+    match e {
+        EjdbError::Locked(_) => true,
+        EjdbError::Generic(rc) | EjdbError::OpenError { rc, .. } => {
+            *rc & 0xFFFF_FFFF == sys::iw_ecode::IW_ERROR_AGAIN as u64 || utils::is_locked(*rc)
+        }
+        EjdbError::IoError(e) => matches!(
+            e.kind(),
+            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ),
+        _ => false,
+    }
+}
+
+/// call `f` up to `times` times while it fails with a retryable error,
+/// see [`is_retryable`]; the delay between calls starts at `backoff`
+/// and doubles after each retry. Other errors are returned at once
+pub fn retry<F, R>(f: F, times: usize, backoff: Duration) -> Result<R>
+where
+    F: FnMut() -> Result<R>,
+{
+    retry_while(f, backoff, |e, attempt| {
+        if attempt < times && is_retryable(e) {
+            Some(Duration::MAX)
+        } else {
+            None
+        }
+    })
+}
+
+/// call `f` while it fails with `EjdbError::Locked` until `timeout` elapses,
+/// with the same backoff as [`retry`]; the last delay is cut to the timeout
+pub(crate) fn retry_locked<F, R>(f: F, timeout: Duration, backoff: Duration) -> Result<R>
+where
+    F: FnMut() -> Result<R>,
+{
+    let deadline = Instant::now() + timeout;
+    retry_while(f, backoff, |e, _| match e {
+        EjdbError::Locked(_) => deadline
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero()),
+        _ => None,
+    })
+}
+
+/// call `f` again while `again` returns the longest delay allowed
+/// for the failed attempt, numbered from 1; the delay starts at
+/// `backoff` and doubles after each retry
+fn retry_while<F, R, A>(mut f: F, backoff: Duration, mut again: A) -> Result<R>
+where
+    F: FnMut() -> Result<R>,
+    A: FnMut(&EjdbError, usize) -> Option<Duration>,
+{
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) => match again(&e, attempt) {
+                Some(max) => {
+                    thread::sleep(delay.min(max));
+                    delay = delay.saturating_mul(2);
+                    attempt += 1;
+                }
+                None => return Err(e),
+            },
+            res => return res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::XString;
+
+    #[test]
+    fn test_retry() {
+        let mut calls = 0;
+        let res = retry(
+            || {
+                calls += 1;
+                if calls < 3 {
+                    Err(EjdbError::Locked(XString::new()))
+                } else {
+                    Ok(calls)
+                }
+            },
+            5,
+            Duration::from_millis(1),
+        );
+        assert_eq!(res.unwrap(), 3);
+
+        let mut calls = 0;
+        let res: Result<()> = retry(
+            || {
+                calls += 1;
+                Err(EjdbError::Locked(XString::new()))
+            },
+            3,
+            Duration::from_millis(1),
+        );
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let res: Result<()> = retry(
+            || {
+                calls += 1;
+                Err(EjdbError::TxnConflict("test"))
+            },
+            3,
+            Duration::from_millis(1),
+        );
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let again = sys::iw_ecode::IW_ERROR_AGAIN as u64;
+        assert!(is_retryable(&EjdbError::Generic(again)));
+        let auth = sys::iw_ecode::IW_ERROR_AUTH as u64;
+        assert!(!is_retryable(&EjdbError::Generic(auth)));
+        assert!(is_retryable(&EjdbError::Locked(XString::new())));
+        let e = io::Error::from(io::ErrorKind::TimedOut);
+        assert!(is_retryable(&EjdbError::IoError(e)));
+        let e = io::Error::from(io::ErrorKind::NotFound);
+        assert!(!is_retryable(&EjdbError::IoError(e)));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{EjdbError, Result};
+use ejdb2_sys as sys;
 
 #[inline(always)]
 pub fn check_rc(rc: u64) -> Result<()> {
@@ -9,6 +10,37 @@ pub fn check_rc(rc: u64) -> Result<()> {
     }
 }
 
+/// JSON has no NaN or infinity, so non-finite floats are rejected
+/// with `EjdbError::InvalidValue`
+#[inline(always)]
+pub fn check_finite(val: f64) -> Result<f64> {
+    if val.is_finite() {
+        Ok(val)
+    } else {
+        Err(EjdbError::InvalidValue(
+            "NaN or infinity is not allowed in JSON",
+        ))
+    }
+}
+
+/// true if rc means document, collection or JSON path not found
+#[inline(always)]
+pub fn is_not_found(rc: u64) -> bool {
+    rc == sys::iwkv_ecode::IWKV_ERROR_NOTFOUND as u64
+        || rc == sys::ejdb_ecode_t::EJDB_ERROR_COLLECTION_NOT_FOUND as u64
+        || rc == sys::jbl_ecode_t::JBL_ERROR_PATH_NOTFOUND as u64
+}
+
+/// true if rc means database file is locked by another process,
+/// which is raised instead of blocking if `file_lock_fail_fast` is set
+#[inline]
+pub fn is_locked(rc: u64) -> bool {
+    // errno is kept in high 32 bits
+    let errno = (rc >> 32) as i32;
+    rc & 0xFFFF_FFFF == sys::iw_ecode::IW_ERROR_ERRNO as u64
+        && (errno == libc::EAGAIN || errno == libc::EACCES)
+}
+
 #[cfg(feature = "std")]
 pub use std::panic::catch_unwind;
 
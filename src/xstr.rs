@@ -11,11 +11,13 @@ use core::{
 };
 
 #[cfg(any(feature = "std", feature = "alloc"))]
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 #[cfg(feature = "std")]
 use std::ffi::{CStr, CString};
 
-/// iwxstr
+/// iwxstr, a growable byte buffer which always keeps a trailing NUL
+/// after its content; the content itself may contain NUL, so `len`
+/// might differ from what C string functions see
 pub struct XString {
     ptr: *mut sys::IWXSTR,
 }
@@ -33,8 +35,26 @@ impl XString {
         Self::from_ptr(ptr)
     }
 
+    /// same as `new_with_size`
+    #[inline(always)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::new_with_size(capacity)
+    }
+
+    /// copy bytes into buffer of exactly the same size;
+    /// iwxstr can not take over memory allocated by rust
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        Self::from(data.as_slice())
+    }
+
     #[inline(always)]
     pub(crate) fn from_ptr(ptr: *mut sys::IWXSTR) -> Self {
+        #[cfg(feature = "alloc-stats")]
+        if !ptr.is_null() {
+            crate::stats::XSTRINGS.inc();
+        }
         Self { ptr }
     }
 
@@ -57,6 +77,18 @@ impl XString {
         unsafe { sys::iwxstr_size(self.as_mut_ptr()) as usize }
     }
 
+    /// content length in bytes, excluding trailing NUL; same as `size`
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.size()
+    }
+
+    /// true if content is empty, same as `size() == 0`
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
     #[inline(always)]
     pub fn clear(&mut self) -> &mut Self {
         unsafe {
@@ -85,6 +117,13 @@ impl XString {
         self
     }
 
+    /// append content of other in place, bytes are copied as is
+    #[inline(always)]
+    pub fn append(&mut self, other: &XString) -> &mut Self {
+        self.push_bytes(other.to_bytes()).unwrap();
+        self
+    }
+
     #[inline(always)]
     pub fn unshift(&mut self, buf: impl AsRef<str>) -> &mut Self {
         self.unshift_bytes(buf.as_ref().as_bytes()).unwrap();
@@ -142,6 +181,15 @@ impl XString {
         }
     }
 
+    /// content with trailing NUL
+    #[inline(always)]
+    pub fn as_bytes_with_nul(&self) -> &[u8] {
+        unsafe {
+            let ptr = sys::iwxstr_ptr(self.as_mut_ptr());
+            slice::from_raw_parts(ptr as *const u8, self.size() + 1)
+        }
+    }
+
     #[inline(always)]
     pub fn to_bytes_mut(&self) -> &mut [u8] {
         unsafe {
@@ -152,17 +200,36 @@ impl XString {
         }
     }
 
+    /// as str without UTF-8 validation;
+    /// the result is undefined behavior if content is not valid UTF-8,
+    /// use `try_as_str` for content from untrusted or binary data
     #[inline(always)]
     pub fn as_str(&self) -> &str {
         let bytes = self.to_bytes();
         unsafe { core::str::from_utf8_unchecked(bytes) }
     }
 
+    /// as str, fails with `EjdbError::Utf8Error` if content is not valid UTF-8
+    #[inline]
+    pub fn try_as_str(&self) -> Result<&str> {
+        let s = core::str::from_utf8(self.to_bytes())?;
+        Ok(s)
+    }
+
+    /// as mutable str without UTF-8 validation;
+    /// the result is undefined behavior if content is not valid UTF-8
     #[inline(always)]
     pub fn as_str_mut(&mut self) -> &mut str {
         let bytes = self.to_bytes_mut();
         unsafe { core::str::from_utf8_unchecked_mut(bytes) }
     }
+
+    /// as mutable str, fails with `EjdbError::Utf8Error` if content is not valid UTF-8
+    #[inline]
+    pub fn try_as_str_mut(&mut self) -> Result<&mut str> {
+        let s = core::str::from_utf8_mut(self.to_bytes_mut())?;
+        Ok(s)
+    }
 }
 
 impl Default for XString {
@@ -198,8 +265,7 @@ impl fmt::Write for XString {
 impl std::io::Write for XString {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.push_bytes(buf)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        self.push_bytes(buf)?;
         Ok(buf.len())
     }
     #[inline(always)]
@@ -220,6 +286,8 @@ impl std::io::Read for XString {
         Ok(len)
     }
 }
+/// C string ends at first NUL, which is the trailing NUL unless
+/// the content contains NUL
 #[cfg(feature = "std")]
 impl AsRef<std::ffi::CStr> for XString {
     #[inline(always)]
@@ -277,7 +345,7 @@ impl core::ops::Add for XString {
     #[inline]
     fn add(self, rhs: Self) -> Self::Output {
         let mut v = self;
-        v.push(rhs.as_str());
+        v.append(&rhs);
         v
     }
 }
@@ -289,6 +357,8 @@ impl Drop for XString {
             unsafe {
                 sys::iwxstr_destroy(self.ptr);
             }
+            #[cfg(feature = "alloc-stats")]
+            crate::stats::XSTRINGS.dec();
         }
     }
 }
@@ -320,7 +390,7 @@ impl From<&str> for XString {
         this
     }
 }
-#[cfg(feature = "std")]
+#[cfg(any(feature = "std", feature = "alloc"))]
 impl From<String> for XString {
     #[inline(always)]
     fn from(s: String) -> Self {
@@ -407,14 +477,14 @@ impl<'a> From<&'a XString> for StringPtr<'a> {
         StringPtr::XStringRef(s)
     }
 }
-#[cfg(feature = "std")]
+#[cfg(any(feature = "std", feature = "alloc"))]
 impl From<String> for StringPtr<'_> {
     #[inline]
     fn from(s: String) -> Self {
         StringPtr::XString(s.into())
     }
 }
-#[cfg(feature = "std")]
+#[cfg(any(feature = "std", feature = "alloc"))]
 impl From<&String> for StringPtr<'_> {
     #[inline]
     fn from(s: &String) -> Self {
@@ -455,6 +525,47 @@ mod test {
         assert_eq!(xstr.size(), buf.len());
     }
 
+    #[test]
+    fn test_try_as_str() {
+        let xstr: XString = "hello"[..].into();
+        assert_eq!(xstr.try_as_str().unwrap(), "hello");
+        let xstr: XString = b"\xff\xfe"[..].into();
+        assert!(matches!(xstr.try_as_str(), Err(EjdbError::Utf8Error(_))));
+    }
+
+    #[test]
+    fn test_nul() {
+        let xstr: XString = b"a\0b"[..].into();
+        assert_eq!(xstr.len(), 3);
+        assert_eq!(xstr.as_bytes_with_nul(), b"a\0b\0");
+        assert_eq!(xstr, "a\0b");
+        assert_ne!(xstr, "a");
+        let cstr: &CStr = xstr.as_ref();
+        assert_eq!(cstr.to_bytes(), b"a");
+        let copy = XString::from_str_ptr(xstr.as_ptr());
+        assert_eq!(copy, "a");
+
+        let xstr = XString::new();
+        assert!(xstr.is_empty());
+        assert_eq!(xstr.as_bytes_with_nul(), b"\0");
+    }
+
+    #[test]
+    fn test_from_vec() {
+        let xstr = XString::from_vec(b"hello".to_vec());
+        assert_eq!(xstr, "hello");
+    }
+
+    #[test]
+    fn test_append() {
+        let mut xstr = XString::from_vec(b"ab".to_vec());
+        let other = XString::from_vec(vec![0xff, b'c']);
+        xstr.append(&other).append(&XString::new());
+        assert_eq!(xstr.to_bytes(), b"ab\xffc");
+        let sum = XString::from_vec(b"x".to_vec()) + XString::from_vec(b"y".to_vec());
+        assert_eq!(sum, "xy");
+    }
+
     #[test]
     fn test_xstr() {
         let mut xstr: XString = XString::new();